@@ -0,0 +1,92 @@
+use std::io;
+use std::sync::Arc;
+
+use raytracer::{
+    adaptive::AdaptiveSampling,
+    camera::Camera,
+    hittable::{BoxObject, HittableList, Sphere},
+    material::{Dielectric, DiffuseLight, Lambertian, Material},
+    quad::Quad,
+    texture::{SolidTexture, Texture},
+    vec3::Vec3,
+};
+
+fn main() -> io::Result<()> {
+    let red = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.65, 0.05, 0.05)));
+    let white = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.73, 0.73, 0.73)));
+    let green = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.12, 0.45, 0.15)));
+    let light = Material::DiffuseLight(DiffuseLight::new(Texture::Solid(SolidTexture::new(
+        Vec3::new(15., 15., 15.),
+    ))));
+
+    let mut world = HittableList::default();
+    world.add(Box::new(Quad::new(
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+        green,
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+        red,
+    )));
+    world.add_light(Arc::new(Quad::new(
+        Vec3::new(343., 554., 332.),
+        Vec3::new(-130., 0., 0.),
+        Vec3::new(0., 0., -105.),
+        light,
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 0.),
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 0., 555.),
+        white.clone(),
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(555., 555., 555.),
+        Vec3::new(-555., 0., 0.),
+        Vec3::new(0., 0., -555.),
+        white.clone(),
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 555.),
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        white.clone(),
+    )));
+    world.add(Box::new(BoxObject::new(
+        Vec3::new(130., 0., 65.),
+        Vec3::new(295., 165., 230.),
+        white.clone(),
+    )));
+    // Caustics under the glass sphere stay noisy long after the walls have converged
+    let glass = Material::Dielectric(Dielectric {
+        refractive_index: 1.5,
+    });
+    let center = Vec3::new(370., 110., 350.);
+    world.add(Box::new(Sphere::new(center, center, 110., glass)));
+
+    let camera = Camera::init()
+        .aspect_ratio(1.)
+        .image_width(300)
+        .max_depth(10)
+        .vertical_fov(40.)
+        .look_from(Vec3::new(278., 278., -800.))
+        .look_to(Vec3::new(278., 278., 0.))
+        .seed(2024)
+        .adaptive_sampling(
+            AdaptiveSampling::new(0.05)
+                .with_min_samples(16)
+                .with_max_samples(256),
+        )
+        .build();
+
+    let (image, counts) = camera.render_to_buffer_with_counts(&world);
+    println!("Average of {:.1} samples per pixel", counts.average());
+    image.save("output/adaptive.png")?;
+    counts.heat_map().save("output/adaptive_samples.png")?;
+
+    Ok(())
+}
@@ -1,14 +1,15 @@
-use raytracer::bvh::Bvh;
+use raytracer::bvh::{Bvh, SplitMethod};
 use raytracer::camera::Camera;
 use raytracer::hittable::{HittableList, Sphere};
 use raytracer::material::{Dielectric, Lambertian, Material, Metal};
 use raytracer::texture::{CheckerTexture, Texture};
-use raytracer::utilities::{random_in_interval, random_num};
+use raytracer::utilities::{random_in_interval, random_num, seeded_rng};
 use raytracer::vec3::Vec3;
 
 fn main() {
     // World
     let mut world = HittableList::default();
+    let mut rng = seeded_rng(2024);
 
     let checker_texture =
         CheckerTexture::new_from_colors(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9));
@@ -24,19 +25,20 @@ fn main() {
 
     for a in -11..11 {
         for b in -11..11 {
-            let sample_material = random_num();
+            let sample_material = random_num(&mut rng);
             let center = Vec3::new(
-                a as f32 + 0.9 * random_num(),
+                a as f32 + 0.9 * random_num(&mut rng),
                 0.2,
-                b as f32 + 0.9 * random_num(),
+                b as f32 + 0.9 * random_num(&mut rng),
             );
 
             if (center - Vec3::new(4., 0.2, 0.)).length() > 0.9 {
                 if sample_material < 0.8 {
                     // Diffuse sphere spawns
-                    let albedo = Vec3::random() * Vec3::random();
+                    let albedo = Vec3::random(&mut rng) * Vec3::random(&mut rng);
                     let sphere_material = Material::Lambertian(Lambertian::new_from_color(albedo));
-                    let center_end = center + Vec3::new(0., random_in_interval(0., 0.5), 0.);
+                    let center_end =
+                        center + Vec3::new(0., random_in_interval(0., 0.5, &mut rng), 0.);
                     world.add(Box::new(Sphere::new(
                         center,
                         center_end,
@@ -45,8 +47,8 @@ fn main() {
                     )));
                 } else if sample_material < 0.95 {
                     // Metal sphere spawns
-                    let albedo = Vec3::random_in_interval(0.5, 1.);
-                    let fuzz = random_in_interval(0., 0.5);
+                    let albedo = Vec3::random_in_interval(0.5, 1., &mut rng);
+                    let fuzz = random_in_interval(0., 0.5, &mut rng);
                     let sphere_material = Material::Metal(Metal { albedo, fuzz });
                     world.add(Box::new(Sphere::new(center, center, 0.2, sphere_material)));
                 } else {
@@ -77,12 +79,11 @@ fn main() {
     let center_2 = Vec3::new(4., 1., 0.);
     world.add(Box::new(Sphere::new(center_2, center_2, 1., material_2)));
 
-    let root_node = Bvh::new(world.objects);
-    let bbox = root_node.bbox;
-    world = HittableList {
-        objects: vec![Box::new(root_node)],
-        bbox,
-    };
+    let root_node = Bvh::init()
+        .split_method(SplitMethod::Sah { bins: 16 })
+        .build_from_list(world);
+    world = HittableList::default();
+    world.add(Box::new(root_node));
     // Camera
     let camera = Camera::new(
         16. / 9.,
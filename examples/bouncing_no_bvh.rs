@@ -4,12 +4,13 @@ use raytracer::camera::Camera;
 use raytracer::hittable::{HittableList, Sphere};
 use raytracer::material::{Dielectric, Lambertian, Material, Metal};
 use raytracer::texture::{CheckerTexture, Texture};
-use raytracer::utilities::{random_in_interval, random_num};
+use raytracer::utilities::{random_in_interval, random_num, seeded_rng};
 use raytracer::vec3::Vec3;
 
 fn main() -> io::Result<()> {
     // World
     let mut world = HittableList::default();
+    let mut rng = seeded_rng(2024);
 
     let checker_texture =
         CheckerTexture::new_from_colors(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9));
@@ -25,19 +26,20 @@ fn main() -> io::Result<()> {
 
     for a in -11..11 {
         for b in -11..11 {
-            let sample_material = random_num();
+            let sample_material = random_num(&mut rng);
             let center = Vec3::new(
-                a as f32 + 0.9 * random_num(),
+                a as f32 + 0.9 * random_num(&mut rng),
                 0.2,
-                b as f32 + 0.9 * random_num(),
+                b as f32 + 0.9 * random_num(&mut rng),
             );
 
             if (center - Vec3::new(4., 0.2, 0.)).length() > 0.9 {
                 if sample_material < 0.8 {
                     // Diffuse sphere spawns
-                    let albedo = Vec3::random() * Vec3::random();
+                    let albedo = Vec3::random(&mut rng) * Vec3::random(&mut rng);
                     let sphere_material = Material::Lambertian(Lambertian::new_from_color(albedo));
-                    let center_end = center + Vec3::new(0., random_in_interval(0., 0.5), 0.);
+                    let center_end =
+                        center + Vec3::new(0., random_in_interval(0., 0.5, &mut rng), 0.);
                     world.add(Box::new(Sphere::new(
                         center,
                         center_end,
@@ -46,8 +48,8 @@ fn main() -> io::Result<()> {
                     )));
                 } else if sample_material < 0.95 {
                     // Metal sphere spawns
-                    let albedo = Vec3::random_in_interval(0.5, 1.);
-                    let fuzz = random_in_interval(0., 0.5);
+                    let albedo = Vec3::random_in_interval(0.5, 1., &mut rng);
+                    let fuzz = random_in_interval(0., 0.5, &mut rng);
                     let sphere_material = Material::Metal(Metal { albedo, fuzz });
                     world.add(Box::new(Sphere::new(center, center, 0.2, sphere_material)));
                 } else {
@@ -0,0 +1,201 @@
+use std::time::{Duration, Instant};
+
+use raytracer::bvh::{Bvh, SplitMethod};
+use raytracer::camera::Camera;
+use raytracer::hittable::{BoxObject, HittableList, Sphere};
+use raytracer::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use raytracer::quad::Quad;
+use raytracer::texture::{CheckerTexture, SolidTexture, Texture};
+use raytracer::utilities::{random_in_interval, random_num, seeded_rng};
+use raytracer::vec3::Vec3;
+
+// Renders of each configuration timed, keeping the fastest to reduce noise
+const RUNS: usize = 3;
+
+fn main() {
+    let bouncing_camera = Camera::init()
+        .aspect_ratio(16. / 9.)
+        .image_width(240)
+        .samples_per_pixel(8)
+        .max_depth(8)
+        .vertical_fov(20.)
+        .look_from(Vec3::new(13., 2., 3.))
+        .look_to(Vec3::new(0., 0., 0.))
+        .defocus_angle(0.6)
+        .focus_distance(10.)
+        .threads(1)
+        .seed(2024)
+        .build();
+    benchmark("bouncing", bouncing_scene, &bouncing_camera);
+
+    let cornell_camera = Camera::init()
+        .aspect_ratio(1.)
+        .image_width(160)
+        .samples_per_pixel(8)
+        .max_depth(8)
+        .vertical_fov(40.)
+        .look_from(Vec3::new(278., 278., -800.))
+        .look_to(Vec3::new(278., 278., 0.))
+        .threads(1)
+        .seed(2024)
+        .build();
+    benchmark("cornell", cornell_scene, &cornell_camera);
+}
+
+// Times the scene without an acceleration structure and with each way of building one
+fn benchmark(name: &str, scene: fn() -> HittableList, camera: &Camera) {
+    let list = time_render(camera, scene);
+    println!("{name:>10} {:>8} {:>10.1?}", "list", list);
+
+    let methods = [
+        ("median", SplitMethod::Median),
+        ("sah", SplitMethod::Sah { bins: 16 }),
+    ];
+    for (label, method) in methods {
+        let time = time_render(camera, || {
+            let bvh = Bvh::init().split_method(method).build_from_list(scene());
+            let mut world = HittableList::default();
+            world.add(Box::new(bvh));
+            world
+        });
+        println!(
+            "{name:>10} {label:>8} {time:>10.1?} ({:.1}x)",
+            list.as_secs_f64() / time.as_secs_f64()
+        );
+    }
+}
+
+fn time_render(camera: &Camera, world: impl Fn() -> HittableList) -> Duration {
+    let world = world();
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            camera.render_to_buffer(&world);
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn bouncing_scene() -> HittableList {
+    let mut world = HittableList::default();
+    let mut rng = seeded_rng(2024);
+
+    let checker_texture =
+        CheckerTexture::new_from_colors(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9));
+    let material_ground = Material::Lambertian(Lambertian::new(Texture::Checker(checker_texture)));
+    let ground_center = Vec3::new(0., -1000., 0.);
+    world.add(Box::new(Sphere::new(
+        ground_center,
+        ground_center,
+        1000.,
+        material_ground,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let sample_material = random_num(&mut rng);
+            let center = Vec3::new(
+                a as f32 + 0.9 * random_num(&mut rng),
+                0.2,
+                b as f32 + 0.9 * random_num(&mut rng),
+            );
+
+            if (center - Vec3::new(4., 0.2, 0.)).length() > 0.9 {
+                let sphere_material = if sample_material < 0.8 {
+                    let albedo = Vec3::random(&mut rng) * Vec3::random(&mut rng);
+                    Material::Lambertian(Lambertian::new_from_color(albedo))
+                } else if sample_material < 0.95 {
+                    let albedo = Vec3::random_in_interval(0.5, 1., &mut rng);
+                    let fuzz = random_in_interval(0., 0.5, &mut rng);
+                    Material::Metal(Metal { albedo, fuzz })
+                } else {
+                    Material::Dielectric(Dielectric {
+                        refractive_index: 1.5,
+                    })
+                };
+                world.add(Box::new(Sphere::new(center, center, 0.2, sphere_material)));
+            }
+        }
+    }
+
+    let glass = Material::Dielectric(Dielectric {
+        refractive_index: 1.5,
+    });
+    let center_0 = Vec3::new(0., 1., 0.);
+    world.add(Box::new(Sphere::new(center_0, center_0, 1., glass)));
+
+    let diffuse = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.4, 0.2, 0.1)));
+    let center_1 = Vec3::new(-4., 1., 0.);
+    world.add(Box::new(Sphere::new(center_1, center_1, 1., diffuse)));
+
+    let metal = Material::Metal(Metal {
+        albedo: Vec3::new(0.7, 0.6, 0.5),
+        fuzz: 0.,
+    });
+    let center_2 = Vec3::new(4., 1., 0.);
+    world.add(Box::new(Sphere::new(center_2, center_2, 1., metal)));
+
+    world
+}
+
+fn cornell_scene() -> HittableList {
+    let mut world = HittableList::default();
+
+    let red = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.65, 0.05, 0.05)));
+    let white = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.73, 0.73, 0.73)));
+    let green = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.12, 0.45, 0.15)));
+    let light = Material::DiffuseLight(DiffuseLight::new(Texture::Solid(SolidTexture::new(
+        Vec3::new(15., 15., 15.),
+    ))));
+
+    world.add(Box::new(Quad::new(
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+        green,
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+        red,
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(343., 554., 332.),
+        Vec3::new(-130., 0., 0.),
+        Vec3::new(0., 0., -105.),
+        light,
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 0.),
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 0., 555.),
+        white.clone(),
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(555., 555., 555.),
+        Vec3::new(-555., 0., 0.),
+        Vec3::new(0., 0., -555.),
+        white.clone(),
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 555.),
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        white.clone(),
+    )));
+
+    world.add(Box::new(BoxObject::new(
+        Vec3::new(130., 0., 65.),
+        Vec3::new(295., 165., 230.),
+        white.clone(),
+    )));
+    world.add(Box::new(BoxObject::new(
+        Vec3::new(265., 0., 295.),
+        Vec3::new(430., 330., 460.),
+        white,
+    )));
+
+    world
+}
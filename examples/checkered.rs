@@ -34,12 +34,9 @@ fn main() -> io::Result<()> {
         material_ground_2,
     )));
 
-    let root_node = Bvh::new(world.objects);
-    let bbox = root_node.bbox;
-    world = HittableList {
-        objects: vec![Box::new(root_node)],
-        bbox,
-    };
+    let root_node = Bvh::new_from_list(world);
+    world = HittableList::default();
+    world.add(Box::new(root_node));
 
     let camera = Camera::init()
         .look_from(Vec3::new(13., 2., 3.))
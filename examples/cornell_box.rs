@@ -1,4 +1,5 @@
 use std::io;
+use std::sync::Arc;
 
 use raytracer::{
     camera::Camera,
@@ -37,7 +38,7 @@ fn main() -> io::Result<()> {
         Vec3::new(0., 0., 555.),
         red,
     )));
-    world.add(Box::new(Quad::new(
+    world.add_light(Arc::new(Quad::new(
         Vec3::new(343., 554., 332.),
         Vec3::new(-130., 0., 0.),
         Vec3::new(0., 0., -105.),
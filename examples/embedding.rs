@@ -0,0 +1,82 @@
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use raytracer::{
+    camera::Camera,
+    hittable::{HittableList, Sphere},
+    material::{DiffuseLight, Lambertian, Material},
+    progress::{CancellationToken, Progress},
+    quad::Quad,
+    texture::{SolidTexture, Texture},
+    vec3::Vec3,
+};
+
+fn main() -> io::Result<()> {
+    let mut world = HittableList::default();
+
+    let ground = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.5, 0.5, 0.5)));
+    let ground_center = Vec3::new(0., -1000., 0.);
+    world.add(Box::new(Sphere::new(
+        ground_center,
+        ground_center,
+        1000.,
+        ground,
+    )));
+    let red = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.65, 0.05, 0.05)));
+    let center = Vec3::new(0., 2., 0.);
+    world.add(Box::new(Sphere::new(center, center, 2., red)));
+
+    let diffuse_light = Material::DiffuseLight(DiffuseLight::new(Texture::Solid(
+        SolidTexture::new(Vec3::new(4., 4., 4.)),
+    )));
+    world.add_light(Arc::new(Quad::new(
+        Vec3::new(3., 1., -2.),
+        Vec3::new(2., 0., 0.),
+        Vec3::new(0., 2., 0.),
+        diffuse_light,
+    )));
+
+    let camera = Camera::init()
+        .aspect_ratio(16. / 9.)
+        .image_width(400)
+        .samples_per_pixel(100)
+        .max_depth(50)
+        .vertical_fov(20.)
+        .look_from(Vec3::new(26., 3., 6.))
+        .look_to(Vec3::new(0., 2., 0.))
+        .background(Vec3::ZERO)
+        .build();
+
+    // Reports as an application embedding the renderer would, and gives up on the
+    // render if it is still going after ten seconds
+    let report = |progress: &Progress| {
+        let percent = 100. * progress.fraction().unwrap_or(0.);
+        let eta = progress.eta().unwrap_or_default().as_secs_f32();
+        eprint!("\r{percent:5.1}% done, {eta:.0}s left  ");
+    };
+    let cancellation = CancellationToken::new();
+    let image = thread::scope(|scope| {
+        scope.spawn(|| {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !cancellation.is_cancelled() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(50));
+            }
+            cancellation.cancel();
+        });
+        let image = camera.render_to_buffer_with(&world, &report, &cancellation);
+        // Lets the watchdog go if the render finished in time
+        cancellation.cancel();
+        image
+    });
+    eprintln!();
+
+    match image {
+        Some(image) => image.save("output/embedding.png"),
+        None => {
+            println!("Render cancelled");
+            Ok(())
+        }
+    }
+}
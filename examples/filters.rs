@@ -0,0 +1,53 @@
+use std::io;
+
+use raytracer::{
+    camera::Camera,
+    filter::Filter,
+    hittable::{HittableList, Sphere},
+    material::{Lambertian, Material},
+    texture::{CheckerTexture, Texture},
+    vec3::Vec3,
+};
+
+fn main() -> io::Result<()> {
+    let mut world = HittableList::default();
+
+    // Checkers shrinking towards the horizon, which alias badly at few samples
+    let checker_texture =
+        CheckerTexture::new_from_colors(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9));
+    let material = Material::Lambertian(Lambertian::new(Texture::Checker(checker_texture)));
+    let center1 = Vec3::new(0., -10., 0.);
+    let center2 = Vec3::new(0., 10., 0.);
+    world.add(Box::new(Sphere::new(
+        center1,
+        center1,
+        10.,
+        material.clone(),
+    )));
+    world.add(Box::new(Sphere::new(center2, center2, 10., material)));
+
+    // The plain average of the samples within each pixel, then filters reaching
+    // further that smooth the aliasing away at the cost of some sharpness, or keep it
+    // with negative lobes that ring around edges
+    let filters = [
+        ("box", Filter::default()),
+        ("tent", Filter::new_tent(1.)),
+        ("gaussian", Filter::new_gaussian(1.5, 0.5)),
+        ("mitchell", Filter::new_mitchell(2., 1. / 3., 1. / 3.)),
+        ("lanczos", Filter::new_lanczos(3.)),
+    ];
+    for (name, filter) in filters {
+        let camera = Camera::init()
+            .image_width(300)
+            .samples_per_pixel(16)
+            .look_from(Vec3::new(13., 2., 3.))
+            .focus_distance(10.)
+            .seed(2024)
+            .filter(filter)
+            .build();
+
+        camera.render_to_file(format!("output/filter_{name}.png"), &world)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::f32::consts::PI;
+use std::io;
+use std::sync::Arc;
+
+use raytracer::{
+    bvh::{Bvh, SplitMethod},
+    camera::Camera,
+    hittable::{BoxObject, Hittable, HittableList, Sphere},
+    instance::Instance,
+    material::{Lambertian, Material},
+    mesh::TriangleMesh,
+    utilities::{random_in_interval, seeded_rng},
+    vec3::Vec3,
+};
+
+// Three stacked cones narrowing towards the top, standing on the origin
+fn canopy() -> TriangleMesh {
+    let segments = 10;
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+
+    for (tier, (base, radius)) in [(0.6, 1.), (1.3, 0.8), (2., 0.55)].into_iter().enumerate() {
+        let first = (tier * (segments + 2)) as u32;
+        let apex = first + segments as u32 + 1;
+        for i in 0..segments {
+            let angle = 2. * PI * i as f32 / segments as f32;
+            positions.push(Vec3::new(radius * angle.cos(), base, radius * angle.sin()));
+        }
+        positions.push(Vec3::new(0., base, 0.));
+        positions.push(Vec3::new(0., base + 1.1, 0.));
+
+        for i in 0..segments as u32 {
+            let next = (i + 1) % segments as u32;
+            indices.push([first + i, apex, first + next]);
+            indices.push([first + next, first + segments as u32, first + i]);
+        }
+    }
+
+    let placeholder = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.5, 0.5, 0.5)));
+    TriangleMesh::new(positions, indices, placeholder)
+}
+
+fn main() -> io::Result<()> {
+    let mut rng = seeded_rng(7);
+
+    // Geometry shared by every tree of the forest
+    let canopy: Arc<dyn Hittable> = Arc::new(canopy());
+    let bark = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.35, 0.22, 0.1)));
+    let trunk: Arc<dyn Hittable> = Arc::new(BoxObject::new(
+        Vec3::new(-0.15, 0., -0.15),
+        Vec3::new(0.15, 0.7, 0.15),
+        bark,
+    ));
+
+    let mut trees = Vec::<Box<dyn Hittable>>::new();
+    for row in 0..20 {
+        for column in 0..25 {
+            let position = Vec3::new(
+                2.2 * (column as f32 - 12.) + random_in_interval(-0.6, 0.6, &mut rng),
+                0.,
+                -2.2 * row as f32 + random_in_interval(-0.6, 0.6, &mut rng),
+            );
+            let rotation = random_in_interval(0., 360., &mut rng);
+            let green = Vec3::new(
+                random_in_interval(0.05, 0.2, &mut rng),
+                random_in_interval(0.3, 0.55, &mut rng),
+                random_in_interval(0.05, 0.15, &mut rng),
+            );
+
+            trees.push(Box::new(
+                Instance::new(trunk.clone())
+                    .with_y_rotation(rotation)
+                    .with_translation(position),
+            ));
+            trees.push(Box::new(
+                Instance::new(canopy.clone())
+                    .with_y_rotation(rotation)
+                    .with_translation(position)
+                    .with_material(Material::Lambertian(Lambertian::new_from_color(green))),
+            ));
+        }
+    }
+
+    let mut world = HittableList::default();
+    let ground_center = Vec3::new(0., -1000., 0.);
+    world.add(Box::new(Sphere::new(
+        ground_center,
+        ground_center,
+        1000.,
+        Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.45, 0.4, 0.25))),
+    )));
+    world.add(Box::new(
+        Bvh::init()
+            .split_method(SplitMethod::Sah { bins: 16 })
+            .build(trees),
+    ));
+
+    let camera = Camera::init()
+        .aspect_ratio(16. / 9.)
+        .image_width(400)
+        .samples_per_pixel(100)
+        .max_depth(20)
+        .vertical_fov(40.)
+        .look_from(Vec3::new(0., 9., 12.))
+        .look_to(Vec3::new(0., 0., -14.))
+        .build();
+
+    camera.render_to_file("output/forest.png", &world)
+}
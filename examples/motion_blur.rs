@@ -0,0 +1,92 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use raytracer::{
+    bvh::Bvh,
+    camera::Camera,
+    hittable::{BoxObject, Hittable, HittableList, Sphere},
+    instance::AnimatedInstance,
+    material::{Lambertian, Material, Metal},
+    matrix::{AnimatedTransform, Keyframe, Quat},
+    obj::load_obj,
+    quad::Quad,
+    texture::{CheckerTexture, Texture},
+    vec3::Vec3,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut objects = HittableList::default();
+
+    let checker =
+        CheckerTexture::new_from_colors(2., Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9));
+    let ground_center = Vec3::new(0., -1000., 0.);
+    objects.add(Box::new(Sphere::new(
+        ground_center,
+        ground_center,
+        1000.,
+        Material::Lambertian(Lambertian::new(Texture::Checker(checker))),
+    )));
+
+    let up = Vec3::new(0., 1., 0.);
+
+    // Box sliding to the right while tumbling over
+    let red = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.7, 0.1, 0.1)));
+    let cube: Arc<dyn Hittable> = Arc::new(BoxObject::new(
+        Vec3::new(-0.5, -0.5, -0.5),
+        Vec3::new(0.5, 0.5, 0.5),
+        red,
+    ));
+    let tumble = AnimatedTransform::new()
+        .keyframe(0., Keyframe::new(Vec3::new(-3., 0.5, 0.)))
+        .keyframe(
+            1.,
+            Keyframe::new(Vec3::new(-2., 0.5, 0.))
+                .with_rotation(Quat::new_from_axis_angle(Vec3::new(0., 0., 1.), -90.)),
+        );
+    objects.add(Box::new(AnimatedInstance::new(cube, tumble)));
+
+    // Brass panel growing out of the ground
+    let panel: Arc<dyn Hittable> = Arc::new(Quad::new(
+        Vec3::new(-0.75, 0., 0.),
+        Vec3::new(1.5, 0., 0.),
+        Vec3::new(0., 1.5, 0.),
+        Material::Metal(Metal::new(Vec3::new(0.9, 0.7, 0.3), 0.1)),
+    ));
+    let grow = AnimatedTransform::new()
+        .keyframe(0., Keyframe::new(Vec3::new(0., 0., -1.5)))
+        .keyframe(
+            1.,
+            Keyframe::new(Vec3::new(0., 0., -1.5)).with_scale(Vec3::new(1., 1.6, 1.)),
+        );
+    objects.add(Box::new(AnimatedInstance::new(panel, grow)));
+
+    // Pyramid spinning in place, with its capstone following along
+    let spin = AnimatedTransform::new()
+        .keyframe(0., Keyframe::new(Vec3::new(2.5, 0., 0.)))
+        .keyframe(
+            1.,
+            Keyframe::new(Vec3::new(2.5, 0., 0.)).with_rotation(Quat::new_from_axis_angle(up, 45.)),
+        );
+    for group in load_obj("assets/pyramid.obj")? {
+        let mesh: Arc<dyn Hittable> = Arc::new(group.mesh);
+        objects.add(Box::new(AnimatedInstance::new(mesh, spin.clone())));
+    }
+
+    let mut world = HittableList::default();
+    world.add(Box::new(Bvh::new_from_list(objects)));
+
+    let camera = Camera::init()
+        .aspect_ratio(16. / 9.)
+        .image_width(400)
+        .samples_per_pixel(100)
+        .max_depth(20)
+        .vertical_fov(30.)
+        .look_from(Vec3::new(0., 3., 10.))
+        .look_to(Vec3::new(0., 0.8, 0.))
+        .defocus_angle(0.)
+        .build();
+
+    camera.render_to_file("output/motion_blur.png", &world)?;
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use std::error::Error;
+
+use raytracer::{
+    bvh::Bvh,
+    camera::Camera,
+    hittable::{HittableList, Sphere},
+    material::{Lambertian, Material},
+    obj::load_obj,
+    texture::{CheckerTexture, Texture},
+    vec3::Vec3,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut objects = HittableList::default();
+
+    let checker =
+        CheckerTexture::new_from_colors(2., Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9));
+    let ground_center = Vec3::new(0., -1000., 0.);
+    objects.add(Box::new(Sphere::new(
+        ground_center,
+        ground_center,
+        1000.,
+        Material::Lambertian(Lambertian::new(Texture::Checker(checker))),
+    )));
+
+    for group in load_obj("assets/pyramid.obj")? {
+        println!(
+            "Loaded {} with {} faces",
+            group.name,
+            group.mesh.face_count()
+        );
+        objects.add(Box::new(group.mesh));
+    }
+
+    let bvh = Bvh::new_from_list(objects);
+    let mut world = HittableList::default();
+    world.add(Box::new(bvh));
+
+    let camera = Camera::init()
+        .aspect_ratio(16. / 9.)
+        .image_width(400)
+        .samples_per_pixel(100)
+        .max_depth(20)
+        .vertical_fov(30.)
+        .look_from(Vec3::new(5., 3., 6.))
+        .look_to(Vec3::new(0., 0.8, 0.))
+        .build();
+
+    camera.render_to_file("output/obj_model.png", &world)?;
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::env;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use raytracer::{
+    camera::Camera,
+    hittable::{HittableList, Sphere},
+    material::{DiffuseLight, Lambertian, Material},
+    progressive::{Progressive, SnapshotInterval},
+    quad::Quad,
+    texture::{PerlinTexture, SolidTexture, Texture},
+    vec3::Vec3,
+};
+
+fn main() -> io::Result<()> {
+    let mut world = HittableList::default();
+
+    let perlin_material =
+        Material::Lambertian(Lambertian::new(Texture::Perlin(PerlinTexture::new(4.))));
+    let ground_center = Vec3::new(0., -1000., 0.);
+    world.add(Box::new(Sphere::new(
+        ground_center,
+        ground_center,
+        1000.,
+        perlin_material.clone(),
+    )));
+    let center = Vec3::new(0., 2., 0.);
+    world.add(Box::new(Sphere::new(center, center, 2., perlin_material)));
+
+    let diffuse_light = Material::DiffuseLight(DiffuseLight::new(Texture::Solid(
+        SolidTexture::new(Vec3::new(4., 4., 4.)),
+    )));
+    world.add_light(Arc::new(Quad::new(
+        Vec3::new(3., 1., -2.),
+        Vec3::new(2., 0., 0.),
+        Vec3::new(0., 2., 0.),
+        diffuse_light,
+    )));
+
+    let camera = Camera::init()
+        .aspect_ratio(16. / 9.)
+        .image_width(400)
+        .max_depth(50)
+        .vertical_fov(20.)
+        .look_from(Vec3::new(26., 3., 6.))
+        .look_to(Vec3::new(0., 2., 0.))
+        .background(Vec3::ZERO)
+        .seed(2024)
+        .build();
+
+    // Refines the image for up to a minute, so output/progressive.png can be opened
+    // while it is still rendering. Running again with --resume carries on from where
+    // the last run stopped
+    let progressive = Progressive::new()
+        .with_samples_per_pass(4)
+        .with_target_samples(1024)
+        .with_time_budget(Duration::from_secs(60))
+        .with_snapshots(
+            "output/progressive.png",
+            SnapshotInterval::Time(Duration::from_secs(5)),
+        )
+        .with_checkpoints(
+            "output/progressive.checkpoint",
+            SnapshotInterval::Time(Duration::from_secs(30)),
+        )
+        .with_resume(env::args().any(|arg| arg == "--resume"));
+    let accumulator = camera.render_progressive(&world, &progressive)?;
+    println!("Stopped at {} samples per pixel", accumulator.samples);
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::io;
+use std::sync::Arc;
+
+use raytracer::{
+    camera::Camera,
+    hittable::{BoxObject, HittableList},
+    material::{DiffuseLight, Lambertian, Material},
+    quad::Quad,
+    sampler::Sampler,
+    texture::{SolidTexture, Texture},
+    vec3::Vec3,
+};
+
+fn main() -> io::Result<()> {
+    let red = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.65, 0.05, 0.05)));
+    let white = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.73, 0.73, 0.73)));
+    let green = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.12, 0.45, 0.15)));
+    let light = Material::DiffuseLight(DiffuseLight::new(Texture::Solid(SolidTexture::new(
+        Vec3::new(15., 15., 15.),
+    ))));
+
+    let mut world = HittableList::default();
+    world.add(Box::new(Quad::new(
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+        green,
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+        red,
+    )));
+    world.add_light(Arc::new(Quad::new(
+        Vec3::new(343., 554., 332.),
+        Vec3::new(-130., 0., 0.),
+        Vec3::new(0., 0., -105.),
+        light,
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 0.),
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 0., 555.),
+        white.clone(),
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(555., 555., 555.),
+        Vec3::new(-555., 0., 0.),
+        Vec3::new(0., 0., -555.),
+        white.clone(),
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(0., 0., 555.),
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        white.clone(),
+    )));
+    world.add(Box::new(BoxObject::new(
+        Vec3::new(130., 0., 65.),
+        Vec3::new(295., 165., 230.),
+        white.clone(),
+    )));
+    world.add(Box::new(BoxObject::new(
+        Vec3::new(265., 0., 295.),
+        Vec3::new(430., 330., 460.),
+        white,
+    )));
+
+    // Same low sample count with every sampler, to compare how their noise looks
+    let samplers = [
+        ("independent", Sampler::Independent),
+        ("stratified", Sampler::Stratified),
+        ("halton", Sampler::Halton),
+        ("sobol", Sampler::Sobol),
+        ("blue_noise", Sampler::BlueNoise),
+    ];
+    for (name, sampler) in samplers {
+        let camera = Camera::init()
+            .aspect_ratio(1.)
+            .image_width(300)
+            .samples_per_pixel(8)
+            .max_depth(10)
+            .vertical_fov(40.)
+            .look_from(Vec3::new(278., 278., -800.))
+            .look_to(Vec3::new(278., 278., 0.))
+            .seed(2024)
+            .sampler(sampler)
+            .build();
+
+        camera.render_to_file(format!("output/sampler_{name}.png"), &world)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::io;
+use std::sync::Arc;
+
+use raytracer::{
+    camera::Camera,
+    hittable::{BoxObject, Hittable, HittableList},
+    instance::AnimatedInstance,
+    material::{Lambertian, Material},
+    matrix::{AnimatedTransform, Keyframe, Quat},
+    shutter::ShutterCurve,
+    vec3::Vec3,
+};
+
+fn main() -> io::Result<()> {
+    // Propeller making a full turn while the shutter is open. Keyframes every quarter
+    // turn keep the interpolated rotation going the same way round
+    let blade = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.8, 0.3, 0.1)));
+    let propeller: Arc<dyn Hittable> = Arc::new(BoxObject::new(
+        Vec3::new(-2., -0.15, -0.05),
+        Vec3::new(2., 0.15, 0.05),
+        blade,
+    ));
+    let axis = Vec3::new(0., 0., 1.);
+    let spin = (0..=4).fold(AnimatedTransform::new(), |spin, quarter| {
+        spin.keyframe(
+            quarter as f32 / 4.,
+            Keyframe::new(Vec3::ZERO)
+                .with_rotation(Quat::new_from_axis_angle(axis, 90. * quarter as f32)),
+        )
+    });
+
+    let mut world = HittableList::default();
+    world.add(Box::new(AnimatedInstance::new(propeller, spin)));
+
+    let curves = [
+        ("box", ShutterCurve::Box),
+        ("triangle", ShutterCurve::Triangle),
+        ("rolling", ShutterCurve::Rolling { exposure: 0.05 }),
+    ];
+    for (name, curve) in curves {
+        let camera = Camera::init()
+            .aspect_ratio(1.)
+            .image_width(200)
+            .samples_per_pixel(50)
+            .max_depth(10)
+            .vertical_fov(30.)
+            .look_from(Vec3::new(0., 0., 10.))
+            .look_to(Vec3::ZERO)
+            .background(Vec3::new(0.7, 0.8, 1.))
+            .shutter_curve(curve)
+            .build();
+
+        camera.render_to_file(format!("output/shutter_{name}.png"), &world)?;
+    }
+
+    Ok(())
+}
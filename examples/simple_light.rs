@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use raytracer::{camera::Camera, hittable::{HittableList, Sphere}, material::{DiffuseLight, Lambertian, Material}, quad::Quad, texture::{PerlinTexture, SolidTexture, Texture}, vec3::Vec3};
 
 fn main() {
@@ -12,7 +14,7 @@ fn main() {
     world.add(Box::new(Sphere::new(Vec3::new(0.,2.,0.), Vec3::new(0.,2.,0.), 2., perlin_material)));
 
     let diffuse_light = Material::DiffuseLight(DiffuseLight::new(Texture::Solid(SolidTexture::new(Vec3::new(4.,4.,4.)))));
-    world.add(Box::new(Quad::new(Vec3::new(3.,1.,-2.), Vec3::new(2.,0.,0.), Vec3::new(0.,2.,0.), diffuse_light)));
+    world.add_light(Arc::new(Quad::new(Vec3::new(3.,1.,-2.), Vec3::new(2.,0.,0.), Vec3::new(0.,2.,0.), diffuse_light)));
 
     let camera = Camera::new(
         16. / 9.,
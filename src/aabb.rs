@@ -114,17 +114,35 @@ impl Aabb {
         }
     }
 
+    pub fn centroid(&self) -> Vec3 {
+        Vec3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    // Empty boxes have no area, which keeps them from skewing surface area
+    // heuristic costs
+    pub fn surface_area(&self) -> f32 {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        if dx < 0. || dy < 0. || dz < 0. {
+            return 0.;
+        }
+        2. * (dx * dy + dy * dz + dz * dx)
+    }
+
     fn pad_to_minimums(&mut self) {
         let delta = 0.0001;
 
         if self.x.size() < delta {
-            self.x.expands(delta);
+            self.x = self.x.expands(delta);
         }
         if self.y.size() < delta {
-            self.y.expands(delta);
+            self.y = self.y.expands(delta);
         }
         if self.z.size() < delta {
-            self.z.expands(delta);
+            self.z = self.z.expands(delta);
         }
     }
 
@@ -0,0 +1,235 @@
+use crate::color::luminance;
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Vec3;
+
+// Offset added to the brightness of a pixel when judging its relative error, so that
+// nearly black pixels do not need an absurd number of samples
+const DARK_PIXEL_OFFSET: f32 = 0.01;
+
+// Colors of the heat map from the fewest samples to the most
+const HEAT_MAP_RAMP: [Vec3; 5] = [
+    Vec3::new(0., 0., 0.05),
+    Vec3::new(0.3, 0., 0.5),
+    Vec3::new(0.9, 0.2, 0.2),
+    Vec3::new(1., 0.6, 0.),
+    Vec3::new(1., 1., 0.7),
+];
+
+// Sample counts adapting to the noise of every pixel. Pixels take samples in batches
+// of `min_samples` until the standard error of their mean brightness falls below
+// `threshold` times the brightness, or until they reach `max_samples`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    pub threshold: f32,
+}
+
+impl AdaptiveSampling {
+    // Adaptive sampling to the given relative error, with between 16 and 1024 samples
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            min_samples: 16,
+            max_samples: 1024,
+            threshold,
+        }
+    }
+
+    // Values below one are treated as one
+    pub fn with_min_samples(mut self, samples: u32) -> Self {
+        self.min_samples = samples.max(1);
+        self.max_samples = self.max_samples.max(self.min_samples);
+        self
+    }
+
+    pub fn with_max_samples(mut self, samples: u32) -> Self {
+        self.max_samples = samples.max(1);
+        self.min_samples = self.min_samples.min(self.max_samples);
+        self
+    }
+
+    // Whether the estimate of a pixel is good enough to stop sampling it
+    pub fn converged(&self, estimate: &PixelEstimate) -> bool {
+        if estimate.count >= self.max_samples {
+            return true;
+        }
+        if estimate.count < self.min_samples {
+            return false;
+        }
+        estimate.relative_error() <= self.threshold
+    }
+}
+
+// Running mean of the radiance of a pixel together with the variance of its
+// brightness, updated one sample at a time with Welford's algorithm
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PixelEstimate {
+    pub count: u32,
+    pub mean: Vec3,
+    mean_luminance: f32,
+    squared_deviations: f32, // Sum of squared differences from the mean brightness
+}
+
+impl PixelEstimate {
+    pub fn add(&mut self, color: Vec3) {
+        self.count += 1;
+        let weight = 1. / self.count as f32;
+        self.mean = self.mean + weight * (color - self.mean);
+
+        let value = luminance(color);
+        let delta = value - self.mean_luminance;
+        self.mean_luminance += weight * delta;
+        self.squared_deviations += delta * (value - self.mean_luminance);
+    }
+
+    // Unbiased sample variance of the brightness
+    pub fn variance(&self) -> f32 {
+        if self.count < 2 {
+            return 0.;
+        }
+        self.squared_deviations / (self.count - 1) as f32
+    }
+
+    // Standard error of the mean brightness relative to the brightness itself
+    pub fn relative_error(&self) -> f32 {
+        if self.count == 0 {
+            return f32::INFINITY;
+        }
+        let standard_error = (self.variance() / self.count as f32).sqrt();
+        standard_error / (self.mean_luminance.abs() + DARK_PIXEL_OFFSET)
+    }
+}
+
+// Number of samples taken for every pixel of a render, row-major
+#[derive(Clone, Debug, Default)]
+pub struct SampleCounts {
+    pub width: u32,
+    pub height: u32,
+    pub counts: Vec<u32>,
+}
+
+impl SampleCounts {
+    pub fn new(width: u32, height: u32, counts: Vec<u32>) -> Self {
+        assert_eq!(
+            counts.len(),
+            (width * height) as usize,
+            "sample count does not match a {width}x{height} image"
+        );
+
+        Self {
+            width,
+            height,
+            counts,
+        }
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.counts.is_empty() {
+            return 0.;
+        }
+        self.counts.iter().map(|&count| count as f64).sum::<f64>() as f32 / self.counts.len() as f32
+    }
+
+    // Image coloring every pixel by its sample count, from dark blue for the fewest
+    // samples taken anywhere over red to pale yellow for the most
+    pub fn heat_map(&self) -> Framebuffer {
+        let min = self.counts.iter().copied().min().unwrap_or(0);
+        let max = self.counts.iter().copied().max().unwrap_or(0);
+        let range = (max - min).max(1) as f32;
+
+        let pixels = self
+            .counts
+            .iter()
+            .map(|&count| {
+                let position = (count - min) as f32 / range * (HEAT_MAP_RAMP.len() - 1) as f32;
+                let index = (position as usize).min(HEAT_MAP_RAMP.len() - 2);
+                let t = position - index as f32;
+                (1. - t) * HEAT_MAP_RAMP[index] + t * HEAT_MAP_RAMP[index + 1]
+            })
+            .collect();
+        Framebuffer::new_from_pixels(self.width, self.height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_matches_the_direct_mean_and_variance() {
+        let colors = [0.2, 0.9, 0.4, 0.4, 1.5].map(|value| Vec3::new(value, value, value * 0.5));
+        let mut estimate = PixelEstimate::default();
+        for color in colors {
+            estimate.add(color);
+        }
+
+        let mean = (1. / 5.) * colors.into_iter().sum::<Vec3>();
+        assert!((estimate.mean - mean).length() < 1e-6);
+
+        let brightness = colors.map(luminance);
+        let mean_brightness = brightness.iter().sum::<f32>() / 5.;
+        let variance = brightness
+            .iter()
+            .map(|value| (value - mean_brightness).powi(2))
+            .sum::<f32>()
+            / 4.;
+        assert!((estimate.variance() - variance).abs() < 1e-6);
+
+        let standard_error = (variance / 5.).sqrt();
+        let relative = standard_error / (mean_brightness + DARK_PIXEL_OFFSET);
+        assert!((estimate.relative_error() - relative).abs() < 1e-6);
+    }
+
+    #[test]
+    fn convergence_respects_the_sample_bounds() {
+        let adaptive = AdaptiveSampling::new(0.1)
+            .with_min_samples(4)
+            .with_max_samples(8);
+        let mut estimate = PixelEstimate::default();
+        assert_eq!(estimate.relative_error(), f32::INFINITY);
+
+        // A constant pixel has no error, but still takes the minimum of samples
+        for _ in 0..3 {
+            estimate.add(Vec3::splat(0.5));
+            assert!(!adaptive.converged(&estimate));
+        }
+        estimate.add(Vec3::splat(0.5));
+        assert!(adaptive.converged(&estimate));
+
+        // A noisy one stops at the maximum
+        let mut noisy = PixelEstimate::default();
+        for sample in 0..8 {
+            assert!(!adaptive.converged(&noisy));
+            noisy.add(Vec3::splat((sample % 2 * 10) as f32));
+        }
+        assert!(adaptive.converged(&noisy));
+    }
+
+    #[test]
+    fn sample_bounds_stay_ordered() {
+        let adaptive = AdaptiveSampling::new(0.1).with_max_samples(8);
+        assert_eq!((adaptive.min_samples, adaptive.max_samples), (8, 8));
+        let adaptive = adaptive.with_min_samples(32);
+        assert_eq!((adaptive.min_samples, adaptive.max_samples), (32, 32));
+        let adaptive = adaptive.with_min_samples(0).with_max_samples(0);
+        assert_eq!((adaptive.min_samples, adaptive.max_samples), (1, 1));
+    }
+
+    #[test]
+    fn heat_map_spans_the_ramp() {
+        let counts = SampleCounts::new(3, 1, vec![16, 40, 64]);
+        assert_eq!(counts.average(), 40.);
+
+        let heat_map = counts.heat_map();
+        let first = heat_map.get(0, 0);
+        let last = heat_map.get(2, 0);
+        assert!((first - HEAT_MAP_RAMP[0]).length() < 1e-6);
+        assert!((last - HEAT_MAP_RAMP[HEAT_MAP_RAMP.len() - 1]).length() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic(expected = "sample count does not match")]
+    fn counts_must_cover_the_image() {
+        SampleCounts::new(2, 2, vec![1, 2, 3]);
+    }
+}
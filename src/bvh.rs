@@ -1,80 +1,563 @@
-use std::cmp::Ordering;
-
 use crate::aabb::Aabb;
-use crate::hittable::{HitData, Hittable};
+use crate::hittable::{HitData, Hittable, HittableList};
 use crate::interval::Interval;
 use crate::ray::Ray;
-use crate::vec3::Dim;
+use crate::utilities::{pick_with_sample, RenderRng};
+use crate::vec3::{Dim, Vec3};
+
+// Relative cost of stepping through a node compared to intersecting one object,
+// used by the surface area heuristic
+const TRAVERSAL_COST: f32 = 0.125;
+
+// Deepest hierarchy the traversal stack can walk. Nodes at this depth become leaves
+// regardless of their size, so degenerate splits cannot overflow the stack
+const MAX_TRAVERSAL_DEPTH: usize = 64;
+
+// Strategies for dividing the objects of a node between its two children
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitMethod {
+    // Sort along the longest axis of the node and split at the median object
+    Median,
+    // Bin object centroids along each axis and take the split minimizing the
+    // surface area heuristic estimate of the traversal cost
+    Sah { bins: usize },
+}
 
+// Bounding volume hierarchy over a set of objects, acting as a single hittable
 pub struct Bvh {
-    pub left: Box<dyn Hittable>,
-    pub right: Option<Box<dyn Hittable>>,
-    pub bbox: Aabb,
+    objects: Vec<Box<dyn Hittable>>,
+    tree: LinearBvh,
 }
 
 impl Bvh {
-    pub fn new(mut objects: Vec<Box<dyn Hittable>>) -> Self {
-        // Build bounding box for span of objects included in this BVH node
-        let bbox = objects.iter().fold(Aabb::empty(), |acc, object| Aabb::new_from_boxes(acc, object.bounding_box()));
+    pub fn init() -> BvhBuilder {
+        BvhBuilder::default()
+    }
 
-        let longest_axis = bbox.longest_axis();
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> Self {
+        Self::init().build(objects)
+    }
 
-        match objects.len() {
-            1 => Self {
-                left: objects.remove(0),
-                right: None,
-                bbox,
-            },
-            2 => Self {
-                left: objects.remove(0),
-                right: Some(objects.remove(0)),
-                bbox,
-            },
-            _ => {
-                objects.sort_by(move |a, b| -> Ordering {
-                    match Self::box_compare(&a, &b, longest_axis) {
-                        true => Ordering::Less,
-                        false => Ordering::Greater,
+    pub fn new_from_list(list: HittableList) -> Self {
+        Self::init().build_from_list(list)
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
+        self.tree.traverse(ray, interval, |index, interval| {
+            let mut temp_hit_data = HitData::default();
+            if !self.objects[index].hit(ray, interval, &mut temp_hit_data, rng) {
+                return None;
+            }
+            let hit_along_ray = temp_hit_data.hit_along_ray;
+            *hit_data = temp_hit_data;
+            Some(hit_along_ray)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounding_box()
+    }
+
+    // Mixture picking each object with the same probability. Only objects in the
+    // leaves the ray reaches can have sampled its direction
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        if self.objects.is_empty() {
+            return 0.;
+        }
+
+        let mut pdf = 0.;
+        let interval = Interval::new(0.001, f32::INFINITY);
+        self.tree.traverse(ray, interval, |index, _| {
+            pdf += self.objects[index].pdf_value(ray, rng);
+            None
+        });
+        pdf / self.objects.len() as f32
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1., 0., 0.);
+        }
+        let (index, sample) = pick_with_sample(sample, self.objects.len());
+        self.objects[index].random(origin, time, sample)
+    }
+}
+
+// Node of a flattened hierarchy. Interior nodes are directly followed by their
+// first child and point to the second
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    bbox: Aabb,
+    offset: u32, // First primitive of a leaf, or second child of an interior node
+    count: u32,  // Zero for interior nodes
+    axis: Dim,   // Axis the primitives of an interior node were split along
+}
+
+// Bounding volume hierarchy stored as an array of nodes in depth-first order. Leaves
+// refer to contiguous runs of primitives, which are kept by the owner of the
+// hierarchy in the order given when it was built
+pub(crate) struct LinearBvh {
+    nodes: Vec<BvhNode>,
+}
+
+impl LinearBvh {
+    pub(crate) fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.bbox)
+    }
+
+    // Walks the nodes hit by the ray, calling `intersect` with the index of every
+    // primitive in the leaves reached and the part of the ray still worth searching.
+    // `intersect` returns the distance along the ray of a hit within that interval,
+    // which then narrows the search for the remaining primitives
+    pub(crate) fn traverse(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        mut intersect: impl FnMut(usize, Interval) -> Option<f32>,
+    ) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut hit_anything = false;
+        let mut closest_hit = interval.max;
+        let mut stack = [0; MAX_TRAVERSAL_DEPTH];
+        let mut stack_size = 0;
+        let mut node_index = 0;
+
+        loop {
+            let node = self.nodes[node_index];
+            if node.bbox.hit(ray, Interval::new(interval.min, closest_hit)) {
+                if node.count > 0 {
+                    let first = node.offset as usize;
+                    for index in first..first + node.count as usize {
+                        if let Some(hit_along_ray) =
+                            intersect(index, Interval::new(interval.min, closest_hit))
+                        {
+                            hit_anything = true;
+                            closest_hit = hit_along_ray;
+                        }
                     }
-                });
-
-                let mid = objects.len() / 2;
-                let left_objects = objects.split_off(mid);
-                let right_objects = objects;
-                Self {
-                    left: Box::new(Self::new(left_objects)),
-                    right: Some(Box::new(Self::new(right_objects))),
-                    bbox,
+                } else {
+                    // Visit the child nearer to the ray origin first so that its hits
+                    // shrink the interval searched in the farther one
+                    let first_child = node_index + 1;
+                    let second_child = node.offset as usize;
+                    let (near, far) = if ray.direction.get(node.axis) < 0. {
+                        (second_child, first_child)
+                    } else {
+                        (first_child, second_child)
+                    };
+                    stack[stack_size] = far;
+                    stack_size += 1;
+                    node_index = near;
+                    continue;
                 }
             }
+
+            if stack_size == 0 {
+                break;
+            }
+            stack_size -= 1;
+            node_index = stack[stack_size];
         }
+
+        hit_anything
     }
+}
 
-    pub fn new_from_list() {}
+pub struct BvhBuilder {
+    split_method: SplitMethod,
+    max_leaf_size: usize,
+}
 
-    fn box_compare(a: &Box<dyn Hittable>, b: &Box<dyn Hittable>, dim: Dim) -> bool {
-        let a_interval = a.bounding_box().get(dim);
-        let b_interval = b.bounding_box().get(dim);
-        a_interval.min < b_interval.min
+impl Default for BvhBuilder {
+    fn default() -> Self {
+        Self {
+            split_method: SplitMethod::Median,
+            max_leaf_size: 2,
+        }
     }
 }
 
-impl Hittable for Bvh {
-    fn hit(&self, ray: Ray, interval: Interval, hit_data: &mut HitData) -> bool {
-        if !self.bbox.hit(ray, interval) {
-            return false;
+// Primitive waiting to be placed in the hierarchy, with its bounds cached
+struct BuildItem {
+    index: usize,
+    bbox: Aabb,
+    centroid: Vec3,
+}
+
+impl BvhBuilder {
+    pub fn split_method(mut self, method: SplitMethod) -> Self {
+        self.split_method = method;
+        self
+    }
+
+    // Largest number of objects intersected one after the other in a leaf instead
+    // of being split further; values below one are treated as one
+    pub fn max_leaf_size(mut self, size: usize) -> Self {
+        self.max_leaf_size = size.max(1);
+        self
+    }
+
+    pub fn build(&self, objects: Vec<Box<dyn Hittable>>) -> Bvh {
+        let boxes: Vec<Aabb> = objects.iter().map(|object| object.bounding_box()).collect();
+        let (tree, order) = self.build_linear(&boxes);
+
+        let mut objects: Vec<Option<Box<dyn Hittable>>> = objects.into_iter().map(Some).collect();
+        let objects = order
+            .into_iter()
+            .filter_map(|index| objects[index].take())
+            .collect();
+
+        Bvh { objects, tree }
+    }
+
+    pub fn build_from_list(&self, list: HittableList) -> Bvh {
+        self.build(list.objects)
+    }
+
+    // Builds a hierarchy over primitives with the given bounding boxes. Also returns
+    // the order the primitives have to be stored in, as indices into `boxes`, so that
+    // the leaves can refer to them by position
+    pub(crate) fn build_linear(&self, boxes: &[Aabb]) -> (LinearBvh, Vec<usize>) {
+        let mut items: Vec<BuildItem> = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, &bbox)| BuildItem {
+                index,
+                bbox,
+                centroid: bbox.centroid(),
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        if !items.is_empty() {
+            self.build_node(&mut items, 0, 0, &mut nodes);
         }
 
-        let left_hit = self.left.hit(ray, interval, hit_data);
-        let right_hit = match &self.right {
-            Some(obj) => obj.hit(ray, interval, hit_data),
-            None => false
+        let order = items.into_iter().map(|item| item.index).collect();
+        (LinearBvh { nodes }, order)
+    }
+
+    // Recursively builds the node for the given items and its children in
+    // depth-first order. The items are reordered in place so that every leaf covers
+    // a contiguous run of them
+    fn build_node(
+        &self,
+        items: &mut [BuildItem],
+        first_item: usize,
+        depth: usize,
+        nodes: &mut Vec<BvhNode>,
+    ) -> usize {
+        let bbox = items.iter().fold(Aabb::empty(), |acc, item| {
+            Aabb::new_from_boxes(acc, item.bbox)
+        });
+        let count = items.len();
+
+        let split = if count == 1 || depth + 1 >= MAX_TRAVERSAL_DEPTH {
+            None
+        } else {
+            let split = match self.split_method {
+                SplitMethod::Sah { bins } => Self::sah_split(items, bbox, bins),
+                SplitMethod::Median => None,
+            };
+            match split {
+                Some(split) => Some(split),
+                // Testing every object is cheaper than any split, or the node is
+                // already small enough
+                None if count <= self.max_leaf_size => None,
+                None => Some(Self::median_split(items, bbox)),
+            }
+        };
+
+        let node_index = nodes.len();
+        let Some((mid, axis)) = split else {
+            nodes.push(BvhNode {
+                bbox,
+                offset: first_item as u32,
+                count: count as u32,
+                axis: Dim::X,
+            });
+            return node_index;
         };
 
-        left_hit || right_hit
+        nodes.push(BvhNode {
+            bbox,
+            offset: 0,
+            count: 0,
+            axis,
+        });
+        let (left_items, right_items) = items.split_at_mut(mid);
+        self.build_node(left_items, first_item, depth + 1, nodes);
+        let second_child = self.build_node(right_items, first_item + mid, depth + 1, nodes);
+        nodes[node_index].offset = second_child as u32;
+
+        node_index
     }
 
-    fn bounding_box(&self) -> Aabb {
-        self.bbox
+    // Partitions the items around the median of the minimum of their boxes along the
+    // longest axis of the node, returning the index of the median and the axis
+    fn median_split(items: &mut [BuildItem], bbox: Aabb) -> (usize, Dim) {
+        let longest_axis = bbox.longest_axis();
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| {
+            a.bbox
+                .get(longest_axis)
+                .min
+                .total_cmp(&b.bbox.get(longest_axis).min)
+        });
+        (mid, longest_axis)
+    }
+
+    // Finds the cheapest binned split over all three axes and partitions the items
+    // around it, returning the index of the first item on the right side and the
+    // axis. Returns None when a leaf is estimated to be cheaper than any split or
+    // when the centroids cannot be separated at all
+    fn sah_split(items: &mut [BuildItem], bbox: Aabb, bins: usize) -> Option<(usize, Dim)> {
+        let bins = bins.max(2);
+        let centroid_bounds = items.iter().fold(Aabb::empty(), |acc, item| {
+            Aabb::new_from_boxes(acc, Aabb::new_from_points(item.centroid, item.centroid))
+        });
+
+        let bin_of = |centroid: Vec3, axis: Dim| -> usize {
+            let extent = centroid_bounds.get(axis);
+            let offset = (centroid.get(axis) - extent.min) / extent.size();
+            ((offset * bins as f32) as usize).min(bins - 1)
+        };
+
+        // Cost and position of the best split found so far, as (cost, axis, bin)
+        let mut best: Option<(f32, Dim, usize)> = None;
+        for axis in Dim::ALL {
+            if centroid_bounds.get(axis).size() <= 0. {
+                continue;
+            }
+
+            let mut counts = vec![0; bins];
+            let mut boxes = vec![Aabb::empty(); bins];
+            for item in items.iter() {
+                let bin = bin_of(item.centroid, axis);
+                counts[bin] += 1;
+                boxes[bin] = Aabb::new_from_boxes(boxes[bin], item.bbox);
+            }
+
+            // Sweep from the right to know the area and count beyond every split
+            let mut right_area = vec![0.; bins];
+            let mut right_count = vec![0; bins];
+            let mut accumulated = Aabb::empty();
+            let mut count = 0;
+            for bin in (1..bins).rev() {
+                accumulated = Aabb::new_from_boxes(accumulated, boxes[bin]);
+                count += counts[bin];
+                right_area[bin] = accumulated.surface_area();
+                right_count[bin] = count;
+            }
+
+            let mut accumulated = Aabb::empty();
+            let mut count = 0;
+            for split in 1..bins {
+                accumulated = Aabb::new_from_boxes(accumulated, boxes[split - 1]);
+                count += counts[split - 1];
+                if count == 0 || right_count[split] == 0 {
+                    continue;
+                }
+
+                let cost = count as f32 * accumulated.surface_area()
+                    + right_count[split] as f32 * right_area[split];
+                if best.map_or(true, |(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        let (cost, axis, split) = best?;
+        let split_cost = TRAVERSAL_COST + cost / bbox.surface_area();
+        if split_cost >= items.len() as f32 {
+            return None;
+        }
+
+        // Move every item left of the split to the front
+        let mut mid = 0;
+        for i in 0..items.len() {
+            if bin_of(items[i].centroid, axis) < split {
+                items.swap(i, mid);
+                mid += 1;
+            }
+        }
+
+        Some((mid, axis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::assert_sampling_is_consistent;
+    use crate::hittable::Sphere;
+    use crate::material::{Lambertian, Material};
+    use crate::utilities::{random_in_interval, seeded_rng};
+    use std::f32::consts::PI;
+
+    // Overlapping spheres of varied sizes scattered through a box
+    fn spheres(count: usize) -> Vec<Box<dyn Hittable>> {
+        let mut rng = seeded_rng(7);
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::splat(0.5)));
+        (0..count)
+            .map(|_| {
+                let center = Vec3::random_in_interval(-10., 10., &mut rng);
+                let radius = random_in_interval(0.1, 2., &mut rng);
+                Box::new(Sphere::new(center, center, radius, material.clone())) as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    // Rays from around the spheres towards random points among them
+    fn rays(count: usize) -> Vec<Ray> {
+        let mut rng = seeded_rng(11);
+        (0..count)
+            .map(|_| {
+                let origin = Vec3::random_in_interval(-15., 15., &mut rng);
+                let target = Vec3::random_in_interval(-10., 10., &mut rng);
+                Ray::new(origin, target - origin, 0.)
+            })
+            .collect()
+    }
+
+    fn closest_hit(object: &dyn Hittable, ray: Ray) -> Option<f32> {
+        let mut hit_data = HitData::default();
+        let interval = Interval::new(0.001, f32::INFINITY);
+        object
+            .hit(ray, interval, &mut hit_data, &mut seeded_rng(0))
+            .then_some(hit_data.hit_along_ray)
+    }
+
+    #[test]
+    fn hierarchies_find_the_same_hits_as_a_list() {
+        let mut list = HittableList::default();
+        for sphere in spheres(200) {
+            list.add(sphere);
+        }
+        let builders = [
+            Bvh::init(),
+            Bvh::init().split_method(SplitMethod::Sah { bins: 12 }),
+            Bvh::init()
+                .split_method(SplitMethod::Sah { bins: 4 })
+                .max_leaf_size(8),
+        ];
+
+        for builder in builders {
+            let bvh = builder.build(spheres(200));
+            let mut hits = 0;
+            for ray in rays(500) {
+                let expected = closest_hit(&list, ray);
+                hits += expected.is_some() as usize;
+                assert_eq!(
+                    closest_hit(&bvh, ray),
+                    expected,
+                    "{:?}",
+                    builder.split_method
+                );
+            }
+            assert!(hits > 100, "too few rays hit anything to compare");
+        }
+    }
+
+    #[test]
+    fn traversal_reaches_every_primitive_once() {
+        // Unit boxes in a row along x, all pierced by one ray down the row
+        let boxes = (0..37)
+            .map(|i| {
+                let corner = Vec3::new(i as f32, 0., 0.);
+                Aabb::new_from_points(corner, corner + Vec3::splat(1.))
+            })
+            .collect::<Vec<_>>();
+        let ray = Ray::new(Vec3::new(-1., 0.5, 0.5), Vec3::new(1., 0., 0.), 0.);
+
+        for builder in [
+            Bvh::init(),
+            Bvh::init().split_method(SplitMethod::Sah { bins: 8 }),
+        ] {
+            let (tree, order) = builder.build_linear(&boxes);
+            let mut visited = Vec::new();
+            let hit = tree.traverse(ray, Interval::new(0., f32::INFINITY), |index, _| {
+                visited.push(order[index]);
+                None
+            });
+
+            assert!(!hit);
+            visited.sort();
+            assert_eq!(visited, (0..boxes.len()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn traversal_stops_searching_past_the_closest_hit() {
+        let boxes = (0..16)
+            .map(|i| {
+                let corner = Vec3::new(i as f32 * 2., 0., 0.);
+                Aabb::new_from_points(corner, corner + Vec3::splat(1.))
+            })
+            .collect::<Vec<_>>();
+        let ray = Ray::new(Vec3::new(-1., 0.5, 0.5), Vec3::new(1., 0., 0.), 0.);
+        let (tree, order) = Bvh::init().build_linear(&boxes);
+
+        // Every primitive reports a hit at its near face, so only the first box and
+        // those sharing its leaf should be intersected
+        let mut visited = 0;
+        let hit = tree.traverse(ray, Interval::new(0., f32::INFINITY), |index, interval| {
+            visited += 1;
+            let distance = 1. + 2. * order[index] as f32;
+            interval.contains(distance).then_some(distance)
+        });
+
+        assert!(hit);
+        assert!(visited <= 2, "visited {visited} primitives");
+    }
+
+    #[test]
+    fn bounding_box_covers_every_object() {
+        let bvh = Bvh::init()
+            .split_method(SplitMethod::Sah { bins: 8 })
+            .build(spheres(50));
+        for sphere in spheres(50) {
+            let bbox = sphere.bounding_box();
+            for axis in Dim::ALL {
+                assert!(bvh.bounding_box().get(axis).min <= bbox.get(axis).min);
+                assert!(bvh.bounding_box().get(axis).max >= bbox.get(axis).max);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_hierarchy_never_hits() {
+        let bvh = Bvh::init().build(Vec::new());
+        assert_eq!(closest_hit(&bvh, rays(1)[0]), None);
+    }
+
+    #[test]
+    fn sampling_matches_the_density() {
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::splat(0.5)));
+        let centers = [-3., 0., 3.].map(|x| Vec3::new(x, 0., -5.));
+        let objects = centers
+            .iter()
+            .map(|&center| {
+                Box::new(Sphere::new(center, center, 1., material.clone())) as Box<dyn Hittable>
+            })
+            .collect();
+
+        // The spheres don't overlap from the origin, so their cones add up
+        let solid_angle = centers
+            .iter()
+            .map(|center| 2. * PI * (1. - (1. - 1. / center.length_squared()).sqrt()))
+            .sum();
+        assert_sampling_is_consistent(&Bvh::new(objects), Vec3::ZERO, solid_angle);
     }
 }
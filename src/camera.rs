@@ -1,19 +1,60 @@
 use core::f32;
 use std::fs;
-use std::fs::File;
 use std::io;
-use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
 
-use indicatif::ProgressIterator;
-use itertools::Itertools;
+use indicatif::ProgressBar;
 
-use crate::color::*;
+use crate::adaptive::{AdaptiveSampling, PixelEstimate, SampleCounts};
+use crate::checkpoint::Checkpoint;
+use crate::filter::{FilmTile, Filter};
+use crate::framebuffer::Framebuffer;
 use crate::hittable::*;
 use crate::interval::*;
+use crate::material::Material;
+use crate::output::OutputFormat;
+use crate::progress::{CancellationToken, ProgressReporter, Quiet, Tracker};
+use crate::progressive::{partial_path, Accumulator, Progressive, Schedule, SnapshotInterval};
 use crate::ray::*;
-use crate::utilities::{degrees_to_radians, random_num};
+use crate::sampler::{PixelSample, Sampler};
+use crate::shutter::{Shutter, ShutterCurve};
+use crate::tonemap::{ToneMapOperator, ToneMapping, TransferCurve};
+use crate::utilities::{degrees_to_radians, sample_rng, RenderRng};
 use crate::vec3::*;
 
+// Side length in pixels of the square image tiles handed out to render threads
+const TILE_SIZE: u32 = 16;
+
+// Samples of every pixel taken in each pass of `render_to_disc`, after which its
+// checkpoint is brought up to date
+const DISC_PASS_SAMPLES: u32 = 16;
+
+// Weighting of the light found both by sampling the lights and by following the
+// scattered ray, so that each strategy counts most where it is the less noisy one
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MisHeuristic {
+    Balance, // Weights in proportion to the densities of both strategies
+    #[default]
+    Power, // Weights in proportion to the squared densities, favouring the likelier one
+}
+
+impl MisHeuristic {
+    // Weight of a sample drawn with the first density against one with the other
+    pub fn weight(&self, pdf: f32, other_pdf: f32) -> f32 {
+        if other_pdf <= 0. {
+            return 1.;
+        }
+        match self {
+            Self::Balance => pdf / (pdf + other_pdf),
+            Self::Power => pdf * pdf / (pdf * pdf + other_pdf * other_pdf),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Camera {
     pub aspect_ratio: f32,
@@ -37,7 +78,17 @@ pub struct Camera {
     pub focus_distance: f32,
     pub defocus_disc_u: Vec3,
     pub defocus_disc_v: Vec3,
-    pub background: Option<Vec3>, // Color for background
+    pub background: Option<Vec3>,    // Color for background
+    pub threads: usize,              // Number of worker threads used for rendering
+    pub seed: u64,                   // Seed from which every random sample of the render is derived
+    pub tone_mapping: ToneMapping,   // Conversion of the rendered radiance for 8-bit outputs
+    pub shutter: Shutter,            // Interval and curve from which ray times are drawn
+    pub mis_heuristic: MisHeuristic, // Weighting of light sampling against scattering
+    pub sampler: Sampler,            // Source of the sample points driving every path
+    pub filter: Filter,              // Weighting of the samples around every pixel
+    // Per-pixel sample counts following the noise, replacing `samples_per_pixel`
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    pub quiet: bool, // Hides the terminal progress bar of renders writing to disc
 }
 
 impl Default for Camera {
@@ -51,6 +102,7 @@ impl Camera {
         CameraBuilder::default()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         aspect_ratio: f32,
         image_width: u32,
@@ -101,45 +153,80 @@ impl Camera {
             defocus_disc_u,
             defocus_disc_v,
             background,
+            threads: default_thread_count(),
+            seed: rand::random(),
+            tone_mapping: ToneMapping::default(),
+            shutter: Shutter::default(),
+            mis_heuristic: MisHeuristic::default(),
+            sampler: Sampler::default(),
+            filter: Filter::default(),
+            adaptive_sampling: None,
+            quiet: false,
         }
     }
 
-    fn sample_square() -> Vec3 {
-        Vec3::new(random_num() - 0.5, random_num() - 0.5, 0.)
+    // Same camera with its shutter moved later in time, to render the frames of an
+    // animation one after the other
+    pub fn with_time_offset(mut self, offset: f32) -> Self {
+        self.shutter = self.shutter.shifted(offset);
+        self
     }
 
-    fn defocus_disc_sample(&self) -> Vec3 {
-        // Returns random point inside camera defocus disc
-        let point = Vec3::random_in_unit_disc();
+    fn defocus_disc_sample(&self, sample: (f32, f32)) -> Vec3 {
+        // Returns point inside camera defocus disc
+        let point = Vec3::concentric_disc(sample);
         self.camera_center + point.x * self.defocus_disc_u + point.y * self.defocus_disc_v
     }
 
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        // Construct a ray starting from the camera defocus disc and pointing to a randomly
-        // sampled location in the i,j pixel
-        let offset = Self::sample_square();
+    fn get_ray(
+        &self,
+        i: u32,
+        j: u32,
+        offset: (f32, f32),
+        samples: &mut PixelSample,
+        rng: &mut RenderRng,
+    ) -> Ray {
+        // Construct a ray starting from the camera defocus disc and pointing to the
+        // location at the given offset from the centre of the i,j pixel
         let pixel_sample = self.pixel00_location
-            + (i as f32 + offset.x) * self.pixel_delta_u
-            + (j as f32 + offset.y) * self.pixel_delta_v;
+            + (i as f32 + offset.0) * self.pixel_delta_u
+            + (j as f32 + offset.1) * self.pixel_delta_v;
 
+        // The lens sample is drawn even for pinhole cameras so that the dimensions of
+        // the path stay in the same place
+        let lens_sample = samples.next_2d(rng);
         let ray_origin = if self.defocus_angle <= 0. {
             self.camera_center
         } else {
-            self.defocus_disc_sample()
+            self.defocus_disc_sample(lens_sample)
         };
-        Ray::new(ray_origin, pixel_sample - ray_origin, random_num())
+        let time = self
+            .shutter
+            .sample(samples.next_1d(rng), j, self.image_height);
+        Ray::new(ray_origin, pixel_sample - ray_origin, time)
     }
 
-    fn ray_color(&self, ray: Ray, depth: u32, world: &HittableList) -> Vec3 {
-        if depth <= 0 {
+    // Radiance arriving along the ray. `scatter_pdf` is the density with which the
+    // previous surface scattered the ray, or None for camera rays and specular
+    // bounces that sampling the lights could not have reproduced
+    fn ray_color(
+        &self,
+        ray: Ray,
+        depth: u32,
+        world: &HittableList,
+        scatter_pdf: Option<f32>,
+        samples: &mut PixelSample,
+        rng: &mut RenderRng,
+    ) -> Vec3 {
+        if depth == 0 {
             return Vec3::new(0., 0., 0.);
         }
 
         let mut hit_data = HitData::default();
 
-        if !world.hit(ray, Interval::new(0.001, f32::INFINITY), &mut hit_data) {
-            return if self.background.is_some() {
-                self.background.unwrap()
+        if !world.hit(ray, Interval::new(0.001, f32::INFINITY), &mut hit_data, rng) {
+            return if let Some(background) = self.background {
+                background
             } else {
                 let unit_direction = ray.direction.unit();
                 let a = 0.5 * (unit_direction.y + 1.);
@@ -150,17 +237,87 @@ impl Camera {
         let mut attenuation = Vec3::default();
         let mut scattered = Ray::default();
         if let Some(material) = hit_data.clone().material {
-            let emitted_color = material.emit(hit_data.point, hit_data.u, hit_data.v);
-            if !material.scatter(ray, &mut hit_data, &mut attenuation, &mut scattered) {
+            let scatter_sample = samples.next_2d(rng);
+            let light_sample = samples.next_2d(rng);
+
+            let mut emitted_color = material.emit(hit_data.point, hit_data.u, hit_data.v);
+            if let Some(scatter_pdf) = scatter_pdf {
+                if !emitted_color.near_zero() {
+                    // The previous bounce may also have reached this light by sampling it
+                    let light_pdf = world.light_pdf_value(ray, rng);
+                    emitted_color =
+                        self.mis_heuristic.weight(scatter_pdf, light_pdf) * emitted_color;
+                }
+            }
+            if !material.scatter(
+                ray,
+                &mut hit_data,
+                &mut attenuation,
+                &mut scattered,
+                scatter_sample,
+            ) {
                 return emitted_color;
             }
 
-            return emitted_color + attenuation * self.ray_color(scattered, depth - 1, world);
+            if material.is_specular() {
+                return emitted_color
+                    + attenuation
+                        * self.ray_color(scattered, depth - 1, world, None, samples, rng);
+            }
+
+            let direct_color =
+                self.direct_light(ray, &hit_data, &material, world, light_sample, rng);
+            let scatter_pdf = material.pdf(ray, &hit_data, scattered.direction);
+            return emitted_color
+                + direct_color
+                + attenuation
+                    * self.ray_color(scattered, depth - 1, world, Some(scatter_pdf), samples, rng);
         }
 
         Vec3::ZERO
     }
 
+    // Light reflected along the ray from a direction sampled towards the registered
+    // lights, weighted against the chance of the material scattering the same way
+    fn direct_light(
+        &self,
+        ray: Ray,
+        hit_data: &HitData,
+        material: &Material,
+        world: &HittableList,
+        sample: (f32, f32),
+        rng: &mut RenderRng,
+    ) -> Vec3 {
+        let Some(direction) = world.random_light_direction(hit_data.point, ray.time, sample) else {
+            return Vec3::ZERO;
+        };
+        let shadow_ray = Ray::new(hit_data.point, direction, ray.time);
+
+        let reflectance = material.eval(ray, hit_data, direction);
+        let light_pdf = world.light_pdf_value(shadow_ray, rng);
+        if light_pdf <= 0. || reflectance.near_zero() {
+            return Vec3::ZERO;
+        }
+
+        let mut light_hit = HitData::default();
+        if !world.hit(
+            shadow_ray,
+            Interval::new(0.001, f32::INFINITY),
+            &mut light_hit,
+            rng,
+        ) {
+            return Vec3::ZERO;
+        }
+        let emitted = light_hit.material.map_or(Vec3::ZERO, |light| {
+            light.emit(light_hit.point, light_hit.u, light_hit.v)
+        });
+
+        let scatter_pdf = material.pdf(ray, hit_data, direction);
+        let weight = self.mis_heuristic.weight(light_pdf, scatter_pdf);
+        (weight / light_pdf) * reflectance * emitted
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn initialize(
         aspect_ratio: f32,
         image_width: u32,
@@ -215,68 +372,554 @@ impl Camera {
         )
     }
 
-    pub fn render(&self, world: &HittableList) {
-        // File creation
-        if let Ok(mut file) = File::create("image.ppm") {
-            let _ = file.write_all(
-                format!("P3\n{} {}\n255\n", self.image_width, self.image_height).as_bytes(),
-            );
-
-            for j in 0..self.image_height {
-                for i in 0..self.image_width {
-                    let mut pixel_color = Vec3::new(0., 0., 0.);
-                    for _sample in 0..self.samples_per_pixel {
-                        let ray = self.get_ray(i, j);
-                        pixel_color += self.ray_color(ray, self.max_depth, world)
-                    }
+    // Radiance of one sample of the pixel and the offset from the centre of the pixel
+    // at which it was taken. `samples_per_pixel` is the number of samples the sampler
+    // spreads evenly over the pixel
+    fn render_sample(
+        &self,
+        x: u32,
+        y: u32,
+        sample: u32,
+        samples_per_pixel: u32,
+        world: &HittableList,
+    ) -> (Vec3, (f32, f32)) {
+        let mut rng = sample_rng(self.seed, x, y, sample);
+        let mut samples = self
+            .sampler
+            .start(self.seed, x, y, sample, samples_per_pixel);
+        let (offset_x, offset_y) = samples.next_2d(&mut rng);
+        let offset = (offset_x - 0.5, offset_y - 0.5);
+        let ray = self.get_ray(x, y, offset, &mut samples, &mut rng);
+        let color = self.ray_color(ray, self.max_depth, world, None, &mut samples, &mut rng);
+        (color, offset)
+    }
 
-                    let _ = file.write_all(&write_color(
-                        (1. / self.samples_per_pixel as f32) * pixel_color,
-                    ));
+    // Splats the samples of the pixel into the tile, returning how many were taken
+    fn render_pixel(&self, x: u32, y: u32, world: &HittableList, tile: &mut FilmTile) -> u32 {
+        let Some(adaptive) = self.adaptive_sampling else {
+            self.render_samples(x, y, 0..self.samples_per_pixel, world, tile);
+            return self.samples_per_pixel;
+        };
 
-                    eprintln!("Write pixel ({i},{j}) successfully")
-                }
+        // Every batch is spread evenly over the pixel on its own, so that stopping
+        // after any batch leaves well distributed samples. The fields are public, so
+        // a batch takes at least one sample even if `min_samples` was set to zero
+        let batch_size = adaptive.min_samples.max(1);
+        let mut estimate = PixelEstimate::default();
+        while !adaptive.converged(&estimate) {
+            let batch_end = (estimate.count + batch_size).min(adaptive.max_samples);
+            while estimate.count < batch_end {
+                let sample = estimate.count;
+                let (color, offset) = self.render_sample(x, y, sample, batch_size, world);
+                tile.add_sample(x, y, offset, color);
+                estimate.add(color);
             }
         }
+        estimate.count
     }
 
-    pub fn render_to_disc(&self, filename: &str, world: &HittableList) -> io::Result<()> {
-        let pixels = (0..self.image_height)
-            .cartesian_product(0..self.image_width)
-            .progress_count((self.image_height * self.image_width) as u64)
-            .map(|(y, x)| {
-                let multisampled_color = (0..self.samples_per_pixel)
+    // Splats the given range of samples of the pixel into the tile, spread evenly over
+    // the pixel as one batch
+    fn render_samples(
+        &self,
+        x: u32,
+        y: u32,
+        samples: Range<u32>,
+        world: &HittableList,
+        tile: &mut FilmTile,
+    ) {
+        let batch_size = samples.len() as u32;
+        for sample in samples {
+            let (color, offset) = self.render_sample(x, y, sample, batch_size, world);
+            tile.add_sample(x, y, offset, color);
+        }
+    }
+
+    // Pixels [x0, x1) x [y0, y1) of the tile, numbered in row-major order across the
+    // image
+    fn tile_bounds(&self, tile: usize) -> (u32, u32, u32, u32) {
+        let tiles_x = self.image_width.div_ceil(TILE_SIZE) as usize;
+        let x0 = (tile % tiles_x) as u32 * TILE_SIZE;
+        let y0 = (tile / tiles_x) as u32 * TILE_SIZE;
+        let x1 = (x0 + TILE_SIZE).min(self.image_width);
+        let y1 = (y0 + TILE_SIZE).min(self.image_height);
+        (x0, y0, x1, y1)
+    }
+
+    fn film_tile(&self, bounds: (u32, u32, u32, u32)) -> FilmTile {
+        FilmTile::new(self.filter, bounds, self.image_width, self.image_height)
+    }
+
+    // Renders the image by splitting it into tiles which are claimed one at a time by
+    // each worker thread. Every tile is computed independently of the others so the
+    // result does not depend on the number of threads or the order in which tiles
+    // complete. The results of the tiles are returned in tile order, or nothing if
+    // the render was cancelled before all tiles were done.
+    fn render_tiles<T: Send>(
+        &self,
+        tracker: &Tracker,
+        render_tile: impl Fn((u32, u32, u32, u32)) -> T + Sync,
+    ) -> Option<Vec<T>> {
+        let tile_count = self.tile_count() as usize;
+        let next_tile = AtomicUsize::new(0);
+
+        let worker = || {
+            let mut finished = Vec::new();
+            loop {
+                if tracker.is_cancelled() {
+                    break finished;
+                }
+                let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                if tile >= tile_count {
+                    break finished;
+                }
+                let bounds = self.tile_bounds(tile);
+                let result = render_tile(bounds);
+                let (x0, y0, x1, y1) = bounds;
+                tracker.tile_finished(((x1 - x0) * (y1 - y0)) as u64);
+                finished.push((tile, result));
+            }
+        };
+
+        let threads = self.threads.clamp(1, tile_count.max(1));
+        let mut finished_tiles: Vec<_> = if threads == 1 {
+            worker()
+        } else {
+            thread::scope(|scope| {
+                let handles = (0..threads)
+                    .map(|_| scope.spawn(worker))
+                    .collect::<Vec<_>>();
+                handles
                     .into_iter()
-                    .map(|_| {
-                        let ray = self.get_ray(x, y);
-                        self.ray_color(ray, self.max_depth, world)
-                    })
-                    .sum::<Vec3>();
-                let multisampled_color = (1. / self.samples_per_pixel as f32) * multisampled_color;
-
-                let final_color = 256.
-                    * (Vec3::new(
-                        linear_to_gamma(multisampled_color.x),
-                        linear_to_gamma(multisampled_color.y),
-                        linear_to_gamma(multisampled_color.z),
-                    )
-                    .clamp(Vec3::ZERO, Vec3::splat(0.999)));
-                format!(
-                    "{} {} {}\n",
-                    final_color.x as u8, final_color.y as u8, final_color.z as u8
-                )
+                    .flat_map(|handle| handle.join().expect("render thread panicked"))
+                    .collect()
             })
-            .collect::<Vec<String>>()
-            .join("");
-
-        fs::write(
-            format!("output/{filename}.ppm"),
-            format!(
-                "P3\n{} {}\n255\n{}",
-                self.image_width, self.image_height, pixels
-            ),
+        };
+
+        if finished_tiles.len() < tile_count {
+            return None;
+        }
+
+        finished_tiles.sort_unstable_by_key(|(tile, _)| *tile);
+        Some(
+            finished_tiles
+                .into_iter()
+                .map(|(_, result)| result)
+                .collect(),
+        )
+    }
+
+    fn tile_count(&self) -> u64 {
+        (self.image_width.div_ceil(TILE_SIZE) * self.image_height.div_ceil(TILE_SIZE)) as u64
+    }
+
+    // Reporter for the renders writing straight to disc, a progress bar in the
+    // terminal unless the camera is quiet
+    fn terminal_progress(&self) -> Box<dyn ProgressReporter> {
+        if self.quiet {
+            Box::new(Quiet)
+        } else {
+            Box::new(ProgressBar::no_length())
+        }
+    }
+
+    // Renders the scene into an in-memory linear HDR image, leaving tone mapping
+    // and encoding to the caller
+    pub fn render_to_buffer(&self, world: &HittableList) -> Framebuffer {
+        self.render_with_progress(world, &Quiet)
+    }
+
+    // Renders the scene like `render_to_buffer` while reporting the progress, giving up
+    // with nothing once the cancellation token is cancelled
+    pub fn render_to_buffer_with(
+        &self,
+        world: &HittableList,
+        progress: &dyn ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Option<Framebuffer> {
+        let tracker = self.tracker(progress, cancellation, 1);
+        self.render_with_counts(world, &tracker)
+            .map(|(image, _)| image)
+    }
+
+    // Renders the scene like `render_to_buffer`, also returning how many samples every
+    // pixel took, which varies from pixel to pixel with adaptive sampling
+    pub fn render_to_buffer_with_counts(
+        &self,
+        world: &HittableList,
+    ) -> (Framebuffer, SampleCounts) {
+        let cancellation = CancellationToken::new();
+        self.render_to_buffer_with_counts_with(world, &Quiet, &cancellation)
+            .expect("render cancelled without a cancellation token")
+    }
+
+    // Renders the scene like `render_to_buffer_with_counts` while reporting the
+    // progress, giving up with nothing once the cancellation token is cancelled
+    pub fn render_to_buffer_with_counts_with(
+        &self,
+        world: &HittableList,
+        progress: &dyn ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Option<(Framebuffer, SampleCounts)> {
+        let tracker = self.tracker(progress, cancellation, 1);
+        self.render_with_counts(world, &tracker)
+    }
+
+    fn render_with_progress(
+        &self,
+        world: &HittableList,
+        progress: &dyn ProgressReporter,
+    ) -> Framebuffer {
+        let cancellation = CancellationToken::new();
+        self.render_to_buffer_with(world, progress, &cancellation)
+            .expect("render cancelled without a cancellation token")
+    }
+
+    fn render_with_counts(
+        &self,
+        world: &HittableList,
+        tracker: &Tracker,
+    ) -> Option<(Framebuffer, SampleCounts)> {
+        let tiles = self.render_tiles(tracker, |bounds| {
+            let mut film = self.film_tile(bounds);
+            let counts = tile_pixels(bounds)
+                .map(|(x, y)| self.render_pixel(x, y, world, &mut film))
+                .collect::<Vec<_>>();
+            (film, counts)
+        });
+        tracker.finish();
+
+        // Merge the tiles in their order, so that pixels splatted into by several
+        // tiles always add them up the same way
+        let mut accumulator = Accumulator::new(self.image_width, self.image_height);
+        let mut counts = vec![0; (self.image_width * self.image_height) as usize];
+        for (tile, (film, tile_counts)) in tiles?.iter().enumerate() {
+            accumulator.add_tile(film);
+            let positions = tile_pixels(self.tile_bounds(tile));
+            for ((x, y), count) in positions.zip(tile_counts) {
+                counts[(y * self.image_width + x) as usize] = *count;
+            }
+        }
+        Some((
+            accumulator.framebuffer(),
+            SampleCounts::new(self.image_width, self.image_height, counts),
+        ))
+    }
+
+    // Tracker of a render going over the whole image the given number of times
+    fn tracker<'a>(
+        &self,
+        progress: &'a dyn ProgressReporter,
+        cancellation: &'a CancellationToken,
+        passes: u64,
+    ) -> Tracker<'a> {
+        let pixels = (self.image_width * self.image_height) as u64;
+        Tracker::new(
+            progress,
+            cancellation,
+            Some(passes * pixels),
+            Some(passes * self.tile_count()),
         )
     }
+
+    // Renders the scene in passes over the whole image, each adding a few samples of
+    // every pixel to the accumulated ones, until the target sample count or the time
+    // budget is reached. Snapshots of the image so far are written along the way, so
+    // a long render can be looked at before it finishes. Adaptive sampling does not
+    // apply to progressive renders. With checkpoints, `progressive` decides where
+    // they are kept and whether an earlier run is resumed; `render_to_disc` keeps
+    // one of its own
+    pub fn render_progressive(
+        &self,
+        world: &HittableList,
+        progressive: &Progressive,
+    ) -> io::Result<Accumulator> {
+        let cancellation = CancellationToken::new();
+        self.render_progressive_with(
+            world,
+            progressive,
+            &*self.terminal_progress(),
+            &cancellation,
+        )
+    }
+
+    // Renders the scene progressively while reporting the progress. Once cancelled,
+    // the pass under way is dropped and the render ends as if its time budget had run
+    // out, with the samples of the finished passes written to the snapshot and the
+    // checkpoint
+    pub fn render_progressive_with(
+        &self,
+        world: &HittableList,
+        progressive: &Progressive,
+        progress: &dyn ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> io::Result<Accumulator> {
+        let checkpoint = match &progressive.checkpoint_path {
+            Some(path) if progressive.resume => match Checkpoint::load(path) {
+                Ok(checkpoint) => Some(checkpoint),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(error) => return Err(error),
+            },
+            _ => None,
+        };
+
+        match checkpoint {
+            Some(checkpoint) => {
+                self.resume_progressive(world, progressive, checkpoint, progress, cancellation)
+            }
+            None => {
+                let accumulator = Accumulator::new(self.image_width, self.image_height);
+                self.render_passes(world, progressive, accumulator, progress, cancellation)
+            }
+        }
+    }
+
+    // Carries on with the samples of the checkpoint. Its seed replaces the one of the
+    // camera, which is usually drawn at random, while settings changing how samples
+    // are laid out must match
+    fn resume_progressive(
+        &self,
+        world: &HittableList,
+        progressive: &Progressive,
+        checkpoint: Checkpoint,
+        progress: &dyn ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> io::Result<Accumulator> {
+        let accumulator = &checkpoint.accumulator;
+        if accumulator.width != self.image_width
+            || accumulator.height != self.image_height
+            || checkpoint.sampler != self.sampler
+            || checkpoint.filter != self.filter
+            || checkpoint.samples_per_pass != progressive.pass_samples()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "checkpoint was rendered with a different image size, sampler, filter or samples per pass",
+            ));
+        }
+        if checkpoint.max_depth != self.max_depth
+            || checkpoint.shutter != self.shutter
+            || checkpoint.mis_heuristic != self.mis_heuristic
+            || checkpoint.tone_mapping != self.tone_mapping
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "checkpoint was rendered with a different depth, shutter, MIS heuristic or tone mapping",
+            ));
+        }
+
+        // A render stopping at a target that is not a whole number of passes ends with
+        // a shorter pass, which an uninterrupted render going further would not have
+        // taken, so carrying on from there could not give the same image
+        if accumulator.samples % checkpoint.samples_per_pass != 0
+            && accumulator.samples < self.target_samples(progressive)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "checkpoint ended with a partial pass and cannot be carried on",
+            ));
+        }
+
+        let camera = Camera {
+            seed: checkpoint.seed,
+            ..*self
+        };
+        camera.render_passes(
+            world,
+            progressive,
+            checkpoint.accumulator,
+            progress,
+            cancellation,
+        )
+    }
+
+    fn render_passes(
+        &self,
+        world: &HittableList,
+        progressive: &Progressive,
+        mut accumulator: Accumulator,
+        progress: &dyn ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> io::Result<Accumulator> {
+        let start = Instant::now();
+        let target_samples = self.target_samples(progressive);
+        // Progress counts the passes left for this run only, which keeps the estimated
+        // time left right when resuming
+        let tracker = if target_samples == u32::MAX {
+            Tracker::new(progress, cancellation, None, None)
+        } else {
+            let remaining = target_samples.saturating_sub(accumulator.samples);
+            let passes = remaining.div_ceil(progressive.pass_samples()) as u64;
+            self.tracker(progress, cancellation, passes)
+        };
+
+        let mut snapshots = Schedule::new(progressive.snapshot_interval);
+        let mut checkpoints = Schedule::new(progressive.checkpoint_interval);
+        let mut passes = 0;
+        while accumulator.samples < target_samples {
+            // At least one pass is always taken, so that a budget spent before the
+            // render even starts still leaves an image rather than a black one
+            if passes > 0
+                && progressive
+                    .time_budget
+                    .is_some_and(|budget| start.elapsed() >= budget)
+            {
+                break;
+            }
+
+            // Passes keep numbering the samples of every pixel where the last one
+            // stopped, so each sample draws fresh random numbers
+            let first = accumulator.samples;
+            let last = first + progressive.pass_samples().min(target_samples - first);
+            let Some(tiles) = self.render_tiles(&tracker, |bounds| {
+                let mut film = self.film_tile(bounds);
+                for (x, y) in tile_pixels(bounds) {
+                    self.render_samples(x, y, first..last, world, &mut film);
+                }
+                film
+            }) else {
+                break;
+            };
+            for tile in &tiles {
+                accumulator.add_tile(tile);
+            }
+            accumulator.samples += last - first;
+            passes += 1;
+
+            let finished = accumulator.samples >= target_samples;
+            if snapshots.pass_finished() && !finished {
+                self.write_snapshot(&accumulator, progressive)?;
+            }
+            if checkpoints.pass_finished() && !finished {
+                self.write_checkpoint(&accumulator, progressive)?;
+            }
+        }
+        tracker.finish();
+
+        // The snapshot and the checkpoint always end up holding the final image
+        self.write_snapshot(&accumulator, progressive)?;
+        self.write_checkpoint(&accumulator, progressive)?;
+        Ok(accumulator)
+    }
+
+    // Samples per pixel a progressive render stops at, unbounded when only its time
+    // budget ends it
+    fn target_samples(&self, progressive: &Progressive) -> u32 {
+        match (progressive.target_samples, progressive.time_budget) {
+            (Some(samples), _) => samples,
+            (None, Some(_)) => u32::MAX,
+            (None, None) => self.samples_per_pixel,
+        }
+    }
+
+    fn write_checkpoint(
+        &self,
+        accumulator: &Accumulator,
+        progressive: &Progressive,
+    ) -> io::Result<()> {
+        let Some(path) = &progressive.checkpoint_path else {
+            return Ok(());
+        };
+        Checkpoint {
+            accumulator: accumulator.clone(),
+            seed: self.seed,
+            sampler: self.sampler,
+            filter: self.filter,
+            samples_per_pass: progressive.pass_samples(),
+            max_depth: self.max_depth,
+            shutter: self.shutter,
+            mis_heuristic: self.mis_heuristic,
+            tone_mapping: self.tone_mapping,
+        }
+        .save(path)
+    }
+
+    fn write_snapshot(
+        &self,
+        accumulator: &Accumulator,
+        progressive: &Progressive,
+    ) -> io::Result<()> {
+        let Some(path) = &progressive.snapshot_path else {
+            return Ok(());
+        };
+        // Written next to the path first, so that viewers never see a half written
+        // snapshot
+        let format = OutputFormat::from_path_or_err(path)?;
+        let partial = partial_path(path);
+        accumulator
+            .framebuffer()
+            .save_tone_mapped(&partial, format, &self.tone_mapping)?;
+        fs::rename(partial, path)
+    }
+
+    pub fn render(&self, world: &HittableList) {
+        let _ = self
+            .render_with_progress(world, &*self.terminal_progress())
+            .write_ppm("image.ppm");
+    }
+
+    // Renders the scene to output/{filename}.ppm in passes, keeping a checkpoint in
+    // output/{filename}.checkpoint after every pass. A run that was killed picks up
+    // from that checkpoint and ends with the same image, and the checkpoint is
+    // removed once the image is written. Adaptive renders can't be split into passes,
+    // so they are rendered in one go without a checkpoint
+    pub fn render_to_disc(&self, filename: &str, world: &HittableList) -> io::Result<()> {
+        let path = format!("output/{filename}.ppm");
+        if self.adaptive_sampling.is_some() {
+            return self
+                .render_with_progress(world, &*self.terminal_progress())
+                .save_tone_mapped(path, OutputFormat::PpmAscii, &self.tone_mapping);
+        }
+
+        let checkpoint_path = format!("output/{filename}.checkpoint");
+        self.render_resumable(path, OutputFormat::PpmAscii, checkpoint_path, world)
+    }
+
+    fn render_resumable(
+        &self,
+        path: impl AsRef<Path>,
+        format: OutputFormat,
+        checkpoint_path: impl AsRef<Path>,
+        world: &HittableList,
+    ) -> io::Result<()> {
+        let checkpoint_path = checkpoint_path.as_ref();
+        let progressive = Progressive::new()
+            .with_samples_per_pass(DISC_PASS_SAMPLES)
+            .with_target_samples(self.samples_per_pixel)
+            .with_checkpoints(checkpoint_path, SnapshotInterval::Passes(1))
+            .with_resume(true);
+        self.render_progressive(world, &progressive)?
+            .framebuffer()
+            .save_tone_mapped(path, format, &self.tone_mapping)?;
+        fs::remove_file(checkpoint_path)
+    }
+
+    // Renders the scene to the given path, choosing the encoding from its extension
+    pub fn render_to_file(&self, path: impl AsRef<Path>, world: &HittableList) -> io::Result<()> {
+        let path = path.as_ref();
+        let format = OutputFormat::from_path_or_err(path)?;
+        self.render_to_file_as(path, format, world)
+    }
+
+    pub fn render_to_file_as(
+        &self,
+        path: impl AsRef<Path>,
+        format: OutputFormat,
+        world: &HittableList,
+    ) -> io::Result<()> {
+        self.render_with_progress(world, &*self.terminal_progress())
+            .save_tone_mapped(path, format, &self.tone_mapping)
+    }
+}
+
+// Pixels of the tile with the given bounds, row by row
+fn tile_pixels((x0, y0, x1, y1): (u32, u32, u32, u32)) -> impl Iterator<Item = (u32, u32)> {
+    (y0..y1).flat_map(move |y| (x0..x1).map(move |x| (x, y)))
+}
+
+// Uses every core available to the process, falling back to a single thread
+fn default_thread_count() -> usize {
+    thread::available_parallelism().map_or(1, |count| count.get())
 }
 
 pub struct CameraBuilder {
@@ -291,6 +934,16 @@ pub struct CameraBuilder {
     defocus_angle: f32,
     focus_distance: f32,
     background: Option<Vec3>,
+    threads: usize,
+    seed: Option<u64>,
+    tone_mapping: ToneMapping,
+    shutter: Shutter,
+    time_offset: f32,
+    mis_heuristic: MisHeuristic,
+    sampler: Sampler,
+    filter: Filter,
+    adaptive_sampling: Option<AdaptiveSampling>,
+    quiet: bool,
 }
 
 impl Default for CameraBuilder {
@@ -307,6 +960,16 @@ impl Default for CameraBuilder {
             defocus_angle: 0.,
             focus_distance: 10.,
             background: None,
+            threads: default_thread_count(),
+            seed: None,
+            tone_mapping: ToneMapping::default(),
+            shutter: Shutter::default(),
+            time_offset: 0.,
+            mis_heuristic: MisHeuristic::default(),
+            sampler: Sampler::default(),
+            filter: Filter::default(),
+            adaptive_sampling: None,
+            quiet: false,
         }
     }
 }
@@ -367,6 +1030,89 @@ impl CameraBuilder {
         self
     }
 
+    // Number of worker threads to render with; values below one are treated as one
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // Fixes the seed of the render so that the same scene always produces the same
+    // image. Without a seed a random one is drawn when the camera is built
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn tone_map(mut self, operator: ToneMapOperator) -> Self {
+        operator.validate();
+        self.tone_mapping.operator = operator;
+        self
+    }
+
+    // Exposure adjustment in EV stops applied before tone mapping
+    pub fn exposure(mut self, stops: f32) -> Self {
+        self.tone_mapping.exposure = stops;
+        self
+    }
+
+    pub fn transfer_curve(mut self, curve: TransferCurve) -> Self {
+        self.tone_mapping.transfer = curve;
+        self
+    }
+
+    // Ray times at which the shutter opens and closes, by default 0 and 1
+    pub fn shutter(mut self, open: f32, close: f32) -> Self {
+        self.shutter.open = open;
+        self.shutter.close = close;
+        self
+    }
+
+    pub fn shutter_curve(mut self, curve: ShutterCurve) -> Self {
+        self.shutter.curve = curve;
+        self
+    }
+
+    // Moves the whole shutter interval later in time, for instance to the start of
+    // an animation frame. Applied when the camera is built, so it holds whatever
+    // order the shutter is set in
+    pub fn time_offset(mut self, offset: f32) -> Self {
+        self.time_offset = offset;
+        self
+    }
+
+    // Heuristic weighting direct light sampling against scattered rays reaching lights
+    pub fn mis_heuristic(mut self, heuristic: MisHeuristic) -> Self {
+        self.mis_heuristic = heuristic;
+        self
+    }
+
+    // Sampler choosing the pixel positions, lens points, times and scattering and
+    // light sample points of every path, by default independent random numbers
+    pub fn sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    // Reconstruction filter weighing the samples splatted into every pixel, by
+    // default a box averaging the samples taken within the pixel
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter.validated();
+        self
+    }
+
+    // Lets every pixel take as many samples as its noise calls for, within the bounds
+    // of the adaptive sampling, instead of `samples_per_pixel`
+    pub fn adaptive_sampling(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.adaptive_sampling = Some(adaptive);
+        self
+    }
+
+    // Keeps renders writing to disc from drawing a progress bar in the terminal
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
     pub fn build(self) -> Camera {
         // Initialize camera characteristics
         let image_height: u32 = (self.image_width as f32 / self.aspect_ratio) as u32;
@@ -421,6 +1167,435 @@ impl CameraBuilder {
             defocus_disc_u,
             defocus_disc_v,
             background: self.background,
+            threads: self.threads,
+            seed: self.seed.unwrap_or_else(rand::random),
+            tone_mapping: self.tone_mapping,
+            shutter: self.shutter.shifted(self.time_offset),
+            mis_heuristic: self.mis_heuristic,
+            sampler: self.sampler,
+            filter: self.filter,
+            adaptive_sampling: self.adaptive_sampling,
+            quiet: self.quiet,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian, Material};
+    use crate::progress::Progress;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    // Diffuse sphere on a diffuse ground under the sky, where every pixel scatters
+    // a few times and so draws plenty of random numbers
+    fn scene() -> HittableList {
+        let mut world = HittableList::default();
+        let ground = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.5, 0.5, 0.5)));
+        let center = Vec3::new(0., -100.5, -1.);
+        world.add(Box::new(Sphere::new(center, center, 100., ground)));
+        let red = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.7, 0.2, 0.2)));
+        let center = Vec3::new(0., 0., -1.);
+        world.add(Box::new(Sphere::new(center, center, 0.5, red)));
+        world
+    }
+
+    fn camera(seed: u64, threads: usize) -> Camera {
+        Camera::init()
+            .aspect_ratio(4. / 3.)
+            .image_width(24)
+            .samples_per_pixel(4)
+            .max_depth(8)
+            .vertical_fov(90.)
+            .look_from(Vec3::ZERO)
+            .look_to(Vec3::new(0., 0., -1.))
+            .seed(seed)
+            .threads(threads)
+            .quiet(true)
+            .build()
+    }
+
+    fn bits(image: &Framebuffer) -> Vec<[u32; 3]> {
+        image
+            .pixels
+            .iter()
+            .map(|pixel| [pixel.x.to_bits(), pixel.y.to_bits(), pixel.z.to_bits()])
+            .collect()
+    }
+
+    fn render(camera: &Camera) -> Vec<[u32; 3]> {
+        bits(&camera.render_to_buffer(&scene()))
+    }
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        // Neither side is a multiple of the tile size, so the last row and column of
+        // tiles are cut short
+        let background = Vec3::new(0.25, 0.5, 0.75);
+        for threads in [1, 3] {
+            let camera = Camera::init()
+                .aspect_ratio(37. / 21.)
+                .image_width(37)
+                .samples_per_pixel(1)
+                .background(background)
+                .threads(threads)
+                .build();
+
+            let image = camera.render_to_buffer(&HittableList::default()).pixels;
+
+            assert_eq!(
+                image.len(),
+                (camera.image_width * camera.image_height) as usize
+            );
+            assert!(image
+                .iter()
+                .all(|pixel| pixel.x == 0.25 && pixel.y == 0.5 && pixel.z == 0.75));
         }
     }
+
+    #[test]
+    fn thread_count_does_not_change_the_image() {
+        assert_eq!(render(&camera(7, 1)), render(&camera(7, 4)));
+    }
+
+    #[test]
+    fn seed_determines_the_image() {
+        assert_eq!(render(&camera(42, 2)), render(&camera(42, 2)));
+        assert_ne!(render(&camera(42, 2)), render(&camera(43, 2)));
+    }
+
+    #[test]
+    fn seeded_render_is_pinned() {
+        // Any change to the random number stream or to how samples draw from it shows
+        // up here. The tolerance only absorbs differences in the math libraries of
+        // different platforms
+        let image = camera(1234, 2).render_to_buffer(&scene()).pixels;
+        let pinned = [
+            (228, Vec3::new(0.33681285, 0.11773934, 0.15)),
+            (396, Vec3::new(0.30061114, 0.38036668, 0.5)),
+            (0, Vec3::new(0.6256224, 0.77537346, 1.)),
+        ];
+        for (index, expected) in pinned {
+            let pixel = image[index];
+            assert!(
+                (pixel - expected).length() < 1e-5,
+                "pixel {index} is {pixel:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn time_offset_holds_in_any_order_with_the_shutter() {
+        let offset_first = Camera::init().time_offset(2.).shutter(0.25, 0.75).build();
+        let shutter_first = Camera::init().shutter(0.25, 0.75).time_offset(2.).build();
+
+        assert_eq!(offset_first.shutter, Shutter::new(2.25, 2.75));
+        assert_eq!(shutter_first.shutter, Shutter::new(2.25, 2.75));
+    }
+
+    #[test]
+    fn mis_weights_of_both_strategies_sum_to_one() {
+        for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+            for (pdf, other_pdf) in [(0.3, 2.), (1., 1.), (5., 0.01)] {
+                let sum = heuristic.weight(pdf, other_pdf) + heuristic.weight(other_pdf, pdf);
+                assert!((sum - 1.).abs() < 1e-6);
+            }
+            // A direction the other strategy can't produce is left to this one
+            assert_eq!(heuristic.weight(0.5, 0.), 1.);
+        }
+    }
+
+    #[test]
+    fn adaptive_pixels_stop_at_their_bounds() {
+        // Every pixel sees only the sky and converges after the first batch, while the
+        // noisy scene may keep sampling up to the maximum
+        let adaptive = AdaptiveSampling::new(0.01)
+            .with_min_samples(4)
+            .with_max_samples(32);
+        let sky = Camera::init()
+            .image_width(8)
+            .adaptive_sampling(adaptive)
+            .build();
+        let (_, counts) = sky.render_to_buffer_with_counts(&HittableList::default());
+        assert!(counts.counts.iter().all(|&count| count == 4));
+
+        let noisy = Camera {
+            adaptive_sampling: Some(adaptive),
+            ..camera(3, 2)
+        };
+        let (_, counts) = noisy.render_to_buffer_with_counts(&scene());
+        assert!(counts.counts.iter().all(|&count| (4..=32).contains(&count)));
+        assert!(counts.counts.iter().any(|&count| count > 4));
+
+        // The bounds are public fields, so a minimum of zero still takes samples
+        let mut adaptive = adaptive;
+        adaptive.min_samples = 0;
+        let zero_min = Camera {
+            adaptive_sampling: Some(adaptive),
+            ..noisy
+        };
+        let (_, counts) = zero_min.render_to_buffer_with_counts(&scene());
+        assert!(counts.counts.iter().all(|&count| (1..=32).contains(&count)));
+    }
+
+    // Empty directory of its own for the files written by one test
+    fn scratch_dir(test: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("raytracer-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("could not create the scratch directory");
+        dir
+    }
+
+    #[test]
+    fn passes_add_up_to_a_single_render() {
+        // Samples keep their numbers across passes, so independent samples come out
+        // the same however they are split up
+        let camera = camera(5, 2);
+        let progressive = Progressive::new().with_target_samples(camera.samples_per_pixel);
+        let accumulator = camera.render_progressive(&scene(), &progressive).unwrap();
+
+        assert_eq!(accumulator.samples, camera.samples_per_pixel);
+        assert_eq!(bits(&accumulator.framebuffer()), render(&camera));
+    }
+
+    #[test]
+    fn zero_samples_per_pass_still_progresses() {
+        let mut progressive = Progressive::new().with_target_samples(3);
+        progressive.samples_per_pass = 0;
+        let accumulator = camera(3, 2)
+            .render_progressive(&scene(), &progressive)
+            .unwrap();
+        assert_eq!(accumulator.samples, 3);
+    }
+
+    #[test]
+    fn spent_time_budget_still_renders_one_pass() {
+        let progressive = Progressive::new()
+            .with_samples_per_pass(2)
+            .with_time_budget(Duration::ZERO);
+        let accumulator = camera(4, 2)
+            .render_progressive(&scene(), &progressive)
+            .unwrap();
+
+        assert_eq!(accumulator.samples, 2);
+        let image = accumulator.framebuffer();
+        assert!(image.pixels.iter().any(|pixel| pixel.x > 0.));
+    }
+
+    #[test]
+    fn snapshots_are_moved_into_place() {
+        let dir = scratch_dir("snapshots");
+        let snapshot = dir.join("snapshot.png");
+        let progressive = Progressive::new()
+            .with_target_samples(4)
+            .with_snapshots(&snapshot, SnapshotInterval::Passes(1));
+        camera(5, 2)
+            .render_progressive(&scene(), &progressive)
+            .unwrap();
+
+        assert!(snapshot.exists());
+        assert!(!dir.join("snapshot.png.partial").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_render() {
+        let dir = scratch_dir("resume");
+        let path = dir.join("render.checkpoint");
+        let camera = camera(11, 2);
+
+        let uninterrupted = Progressive::new()
+            .with_samples_per_pass(2)
+            .with_target_samples(8);
+        let expected = camera.render_progressive(&scene(), &uninterrupted).unwrap();
+
+        let first_run = uninterrupted
+            .clone()
+            .with_target_samples(4)
+            .with_checkpoints(&path, SnapshotInterval::Passes(1));
+        camera.render_progressive(&scene(), &first_run).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap().accumulator.samples, 4);
+
+        // The resumed run takes the seed of the checkpoint over the one of its camera
+        let second_run = first_run.with_target_samples(8).with_resume(true);
+        let resumed = Camera { seed: 99, ..camera }
+            .render_progressive(&scene(), &second_run)
+            .unwrap();
+
+        assert_eq!(resumed.samples, 8);
+        assert_eq!(bits(&resumed.framebuffer()), bits(&expected.framebuffer()));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn checkpoint_ending_in_a_partial_pass_is_not_carried_on() {
+        let dir = scratch_dir("partial-pass");
+        let path = dir.join("render.checkpoint");
+        let camera = camera(12, 2);
+
+        let first_run = Progressive::new()
+            .with_samples_per_pass(2)
+            .with_target_samples(3)
+            .with_checkpoints(&path, SnapshotInterval::Passes(1));
+        camera.render_progressive(&scene(), &first_run).unwrap();
+
+        let finished = first_run.clone().with_resume(true);
+        let accumulator = camera.render_progressive(&scene(), &finished).unwrap();
+        assert_eq!(accumulator.samples, 3);
+
+        let further = first_run.with_target_samples(6).with_resume(true);
+        let error = camera.render_progressive(&scene(), &further).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn checkpoint_of_other_settings_is_rejected() {
+        let dir = scratch_dir("mismatch");
+        let path = dir.join("render.checkpoint");
+        let camera = camera(13, 2);
+        let progressive = Progressive::new()
+            .with_target_samples(2)
+            .with_checkpoints(&path, SnapshotInterval::Passes(1));
+        camera.render_progressive(&scene(), &progressive).unwrap();
+
+        let resume = progressive.with_target_samples(4).with_resume(true);
+        let tone_mapping = ToneMapping {
+            exposure: 1.,
+            ..camera.tone_mapping
+        };
+        let others = [
+            Camera {
+                max_depth: 3,
+                ..camera
+            },
+            Camera {
+                shutter: Shutter::new(0., 0.5),
+                ..camera
+            },
+            Camera {
+                shutter: camera.shutter.shifted(1.),
+                ..camera
+            },
+            Camera {
+                shutter: Shutter {
+                    curve: ShutterCurve::Triangle,
+                    ..camera.shutter
+                },
+                ..camera
+            },
+            Camera {
+                mis_heuristic: MisHeuristic::Balance,
+                ..camera
+            },
+            Camera {
+                tone_mapping,
+                ..camera
+            },
+            Camera {
+                sampler: Sampler::Sobol,
+                ..camera
+            },
+            Camera {
+                filter: Filter::new_tent(1.),
+                ..camera
+            },
+        ];
+        for other in others {
+            let error = other.render_progressive(&scene(), &resume).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert_eq!(
+            camera
+                .render_progressive(&scene(), &resume)
+                .unwrap()
+                .samples,
+            4
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn disc_renders_pick_up_their_checkpoint() {
+        // Enough samples for several passes, the first of which a killed run left
+        let dir = scratch_dir("disc");
+        let checkpoint = dir.join("render.checkpoint");
+        let camera = Camera {
+            samples_per_pixel: 2 * DISC_PASS_SAMPLES + 3,
+            ..camera(14, 2)
+        };
+        let expected = dir.join("expected.pfm");
+        camera
+            .render_resumable(&expected, OutputFormat::Pfm, &checkpoint, &scene())
+            .unwrap();
+        assert!(!checkpoint.exists());
+
+        let killed = Progressive::new()
+            .with_samples_per_pass(DISC_PASS_SAMPLES)
+            .with_target_samples(DISC_PASS_SAMPLES)
+            .with_checkpoints(&checkpoint, SnapshotInterval::Passes(1));
+        camera.render_progressive(&scene(), &killed).unwrap();
+        let resumed = dir.join("resumed.pfm");
+        camera
+            .render_resumable(&resumed, OutputFormat::Pfm, &checkpoint, &scene())
+            .unwrap();
+
+        assert_eq!(fs::read(resumed).unwrap(), fs::read(expected).unwrap());
+        assert!(!checkpoint.exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn counts_render_reports_every_pixel() {
+        let camera = camera(21, 1);
+        let reports = Mutex::new(Vec::new());
+        let report = |progress: &Progress| reports.lock().unwrap().push(progress.pixels_done);
+
+        let (image, counts) = camera
+            .render_to_buffer_with_counts_with(&scene(), &report, &CancellationToken::new())
+            .expect("render should not be cancelled");
+
+        let reports = reports.into_inner().unwrap();
+        let pixels = (image.width * image.height) as u64;
+        assert!(reports.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(reports.last(), Some(&pixels));
+        assert_eq!(counts.counts.len() as u64, pixels);
+    }
+
+    #[test]
+    fn cancelled_renders_return_nothing() {
+        let camera = camera(22, 2);
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let report = |_: &Progress| panic!("no tile should be rendered");
+
+        assert!(camera
+            .render_to_buffer_with(&scene(), &report, &cancellation)
+            .is_none());
+        assert!(camera
+            .render_to_buffer_with_counts_with(&scene(), &report, &cancellation)
+            .is_none());
+    }
+
+    #[test]
+    fn cancelled_progressive_render_keeps_the_finished_passes() {
+        // The first pass cancels the render as it finishes, so the second is dropped
+        let camera = camera(23, 1);
+        let cancellation = CancellationToken::new();
+        let tiles = camera.tile_count();
+        let report = |progress: &Progress| {
+            if progress.tiles_done == tiles {
+                cancellation.cancel();
+            }
+        };
+        let progressive = Progressive::new()
+            .with_samples_per_pass(2)
+            .with_target_samples(8);
+
+        let accumulator = camera
+            .render_progressive_with(&scene(), &progressive, &report, &cancellation)
+            .unwrap();
+        assert_eq!(accumulator.samples, 2);
+    }
 }
@@ -0,0 +1,399 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::camera::MisHeuristic;
+use crate::filter::Filter;
+use crate::progressive::{partial_path, Accumulator};
+use crate::sampler::Sampler;
+use crate::shutter::{Shutter, ShutterCurve};
+use crate::tonemap::{ToneMapOperator, ToneMapping, TransferCurve};
+use crate::vec3::Vec3;
+
+// Leading bytes of every checkpoint file, including the version of the layout
+const MAGIC: &[u8; 8] = b"RTCHKPT2";
+
+// State of an unfinished progressive render, from which it can carry on exactly as
+// if it had never stopped. Besides the accumulated samples it records everything
+// deciding which random numbers the remaining samples draw, and the settings of the
+// camera that change what the samples add up to
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub accumulator: Accumulator,
+    pub seed: u64,
+    pub sampler: Sampler,
+    pub filter: Filter,
+    pub samples_per_pass: u32,
+    pub max_depth: u32,
+    pub shutter: Shutter, // With the time offset of the camera already applied
+    pub mis_heuristic: MisHeuristic,
+    pub tone_mapping: ToneMapping,
+}
+
+impl Checkpoint {
+    // Writes the checkpoint next to the path first and then moves it into place, so
+    // that a render killed while saving still leaves the previous checkpoint intact
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let partial = partial_path(path);
+
+        let mut file = BufWriter::new(File::create(&partial)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&self.accumulator.width.to_le_bytes())?;
+        file.write_all(&self.accumulator.height.to_le_bytes())?;
+        file.write_all(&self.accumulator.samples.to_le_bytes())?;
+        file.write_all(&self.samples_per_pass.to_le_bytes())?;
+        file.write_all(&self.seed.to_le_bytes())?;
+        file.write_all(&[sampler_id(self.sampler)])?;
+        let (filter, parameters) = filter_parameters(self.filter);
+        file.write_all(&[filter])?;
+        for parameter in parameters {
+            file.write_all(&parameter.to_le_bytes())?;
+        }
+        file.write_all(&self.max_depth.to_le_bytes())?;
+        write_shutter(&mut file, &self.shutter)?;
+        file.write_all(&[mis_heuristic_id(self.mis_heuristic)])?;
+        write_tone_mapping(&mut file, &self.tone_mapping)?;
+        let pixels = self.accumulator.sums.iter().zip(&self.accumulator.weights);
+        for (sum, weight) in pixels {
+            for component in [sum.x, sum.y, sum.z, *weight] {
+                file.write_all(&component.to_le_bytes())?;
+            }
+        }
+        file.into_inner()?.sync_all()?;
+
+        fs::rename(partial, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a render checkpoint"));
+        }
+
+        let width = read_u32(&mut file)?;
+        let height = read_u32(&mut file)?;
+        let samples = read_u32(&mut file)?;
+        let samples_per_pass = read_u32(&mut file)?;
+        let mut seed = [0; 8];
+        file.read_exact(&mut seed)?;
+        let sampler = sampler_from_id(read_u8(&mut file)?)
+            .ok_or_else(|| invalid_data("unknown sampler in checkpoint"))?;
+        let filter = read_u8(&mut file)?;
+        let parameters = [
+            read_f32(&mut file)?,
+            read_f32(&mut file)?,
+            read_f32(&mut file)?,
+        ];
+        let filter = filter_from_parameters(filter, parameters)
+            .filter(Filter::is_valid)
+            .ok_or_else(|| invalid_data("unknown or invalid filter in checkpoint"))?;
+        let max_depth = read_u32(&mut file)?;
+        let shutter = read_shutter(&mut file)?;
+        let mis_heuristic = mis_heuristic_from_id(read_u8(&mut file)?)
+            .ok_or_else(|| invalid_data("unknown MIS heuristic in checkpoint"))?;
+        let tone_mapping = read_tone_mapping(&mut file)?;
+
+        let pixels = (0..width as u64 * height as u64)
+            .map(|_| {
+                let sum = Vec3::new(
+                    read_f32(&mut file)?,
+                    read_f32(&mut file)?,
+                    read_f32(&mut file)?,
+                );
+                Ok((sum, read_f32(&mut file)?))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let (sums, weights) = pixels.into_iter().unzip();
+        if file.read(&mut [0])? != 0 {
+            return Err(invalid_data("trailing data after checkpoint"));
+        }
+
+        Ok(Self {
+            accumulator: Accumulator {
+                width,
+                height,
+                samples,
+                sums,
+                weights,
+            },
+            seed: u64::from_le_bytes(seed),
+            sampler,
+            filter,
+            samples_per_pass,
+            max_depth,
+            shutter,
+            mis_heuristic,
+            tone_mapping,
+        })
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    read_u32(reader).map(f32::from_bits)
+}
+
+// Stable numbering of the samplers in checkpoint files
+fn sampler_id(sampler: Sampler) -> u8 {
+    match sampler {
+        Sampler::Independent => 0,
+        Sampler::Stratified => 1,
+        Sampler::Halton => 2,
+        Sampler::Sobol => 3,
+        Sampler::BlueNoise => 4,
+    }
+}
+
+fn sampler_from_id(id: u8) -> Option<Sampler> {
+    match id {
+        0 => Some(Sampler::Independent),
+        1 => Some(Sampler::Stratified),
+        2 => Some(Sampler::Halton),
+        3 => Some(Sampler::Sobol),
+        4 => Some(Sampler::BlueNoise),
+        _ => None,
+    }
+}
+
+// Shutters are stored as the interval, the curve and the exposure of rolling ones
+fn write_shutter(writer: &mut impl Write, shutter: &Shutter) -> io::Result<()> {
+    let (curve, exposure) = match shutter.curve {
+        ShutterCurve::Box => (0, 0.),
+        ShutterCurve::Triangle => (1, 0.),
+        ShutterCurve::Rolling { exposure } => (2, exposure),
+    };
+    writer.write_all(&shutter.open.to_le_bytes())?;
+    writer.write_all(&shutter.close.to_le_bytes())?;
+    writer.write_all(&[curve])?;
+    writer.write_all(&exposure.to_le_bytes())
+}
+
+fn read_shutter(reader: &mut impl Read) -> io::Result<Shutter> {
+    let open = read_f32(reader)?;
+    let close = read_f32(reader)?;
+    let curve = read_u8(reader)?;
+    let exposure = read_f32(reader)?;
+    let curve = match curve {
+        0 => ShutterCurve::Box,
+        1 => ShutterCurve::Triangle,
+        2 => ShutterCurve::Rolling { exposure },
+        _ => return Err(invalid_data("unknown shutter curve in checkpoint")),
+    };
+    Ok(Shutter { open, close, curve })
+}
+
+fn mis_heuristic_id(heuristic: MisHeuristic) -> u8 {
+    match heuristic {
+        MisHeuristic::Balance => 0,
+        MisHeuristic::Power => 1,
+    }
+}
+
+fn mis_heuristic_from_id(id: u8) -> Option<MisHeuristic> {
+    match id {
+        0 => Some(MisHeuristic::Balance),
+        1 => Some(MisHeuristic::Power),
+        _ => None,
+    }
+}
+
+// Tone mappings are stored as the operator with the white point of the extended
+// Reinhard one, the exposure and the transfer curve
+fn write_tone_mapping(writer: &mut impl Write, tone_mapping: &ToneMapping) -> io::Result<()> {
+    let (operator, white_point) = match tone_mapping.operator {
+        ToneMapOperator::Clamp => (0, 0.),
+        ToneMapOperator::Reinhard => (1, 0.),
+        ToneMapOperator::ExtendedReinhard { white_point } => (2, white_point),
+        ToneMapOperator::Aces => (3, 0.),
+        ToneMapOperator::Hable => (4, 0.),
+    };
+    let transfer = match tone_mapping.transfer {
+        TransferCurve::Linear => 0,
+        TransferCurve::Gamma2 => 1,
+        TransferCurve::Srgb => 2,
+    };
+    writer.write_all(&[operator])?;
+    writer.write_all(&white_point.to_le_bytes())?;
+    writer.write_all(&tone_mapping.exposure.to_le_bytes())?;
+    writer.write_all(&[transfer])
+}
+
+fn read_tone_mapping(reader: &mut impl Read) -> io::Result<ToneMapping> {
+    let operator = read_u8(reader)?;
+    let white_point = read_f32(reader)?;
+    let exposure = read_f32(reader)?;
+    let transfer = read_u8(reader)?;
+    let operator = match operator {
+        0 => ToneMapOperator::Clamp,
+        1 => ToneMapOperator::Reinhard,
+        2 => ToneMapOperator::ExtendedReinhard { white_point },
+        3 => ToneMapOperator::Aces,
+        4 => ToneMapOperator::Hable,
+        _ => return Err(invalid_data("unknown tone map operator in checkpoint")),
+    };
+    let transfer = match transfer {
+        0 => TransferCurve::Linear,
+        1 => TransferCurve::Gamma2,
+        2 => TransferCurve::Srgb,
+        _ => return Err(invalid_data("unknown transfer curve in checkpoint")),
+    };
+    Ok(ToneMapping {
+        operator,
+        exposure,
+        transfer,
+    })
+}
+
+// Stable numbering of the filters in checkpoint files, followed by their parameters
+fn filter_parameters(filter: Filter) -> (u8, [f32; 3]) {
+    match filter {
+        Filter::Box { radius } => (0, [radius, 0., 0.]),
+        Filter::Tent { radius } => (1, [radius, 0., 0.]),
+        Filter::Gaussian { radius, sigma } => (2, [radius, sigma, 0.]),
+        Filter::Mitchell { radius, b, c } => (3, [radius, b, c]),
+        Filter::Lanczos { radius } => (4, [radius, 0., 0.]),
+    }
+}
+
+fn filter_from_parameters(id: u8, [radius, first, second]: [f32; 3]) -> Option<Filter> {
+    match id {
+        0 => Some(Filter::Box { radius }),
+        1 => Some(Filter::Tent { radius }),
+        2 => Some(Filter::Gaussian {
+            radius,
+            sigma: first,
+        }),
+        3 => Some(Filter::Mitchell {
+            radius,
+            b: first,
+            c: second,
+        }),
+        4 => Some(Filter::Lanczos { radius }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn checkpoint() -> Checkpoint {
+        let accumulator = Accumulator {
+            width: 3,
+            height: 2,
+            samples: 4,
+            sums: (0..6).map(|i| Vec3::new(i as f32, 0.5, -1e-3)).collect(),
+            weights: (0..6).map(|i| 4. - 0.25 * i as f32).collect(),
+        };
+        Checkpoint {
+            accumulator,
+            seed: 0xDEAD_BEEF_1234,
+            sampler: Sampler::BlueNoise,
+            filter: Filter::new_mitchell(2., 0.5, 0.25),
+            samples_per_pass: 4,
+            max_depth: 12,
+            shutter: Shutter {
+                curve: ShutterCurve::Rolling { exposure: 0.25 },
+                ..Shutter::new(1., 1.5)
+            },
+            mis_heuristic: MisHeuristic::Balance,
+            tone_mapping: ToneMapping {
+                operator: ToneMapOperator::new_extended_reinhard(4.),
+                exposure: -0.5,
+                transfer: TransferCurve::Srgb,
+            },
+        }
+    }
+
+    fn scratch_file(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!(
+            "raytracer-{name}-{}.checkpoint",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn checkpoint_survives_a_round_trip() {
+        let path = scratch_file("round-trip");
+        let saved = checkpoint();
+        saved.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert!(!partial_path(&path).exists());
+        assert_eq!(
+            (loaded.accumulator.width, loaded.accumulator.height),
+            (3, 2)
+        );
+        assert_eq!(loaded.accumulator.samples, 4);
+        assert!(loaded
+            .accumulator
+            .sums
+            .iter()
+            .zip(&saved.accumulator.sums)
+            .all(|(a, b)| [a.x, a.y, a.z] == [b.x, b.y, b.z]));
+        assert_eq!(loaded.accumulator.weights, saved.accumulator.weights);
+        assert_eq!(loaded.seed, saved.seed);
+        assert_eq!(loaded.sampler, saved.sampler);
+        assert_eq!(loaded.filter, saved.filter);
+        assert_eq!(loaded.samples_per_pass, saved.samples_per_pass);
+        assert_eq!(loaded.max_depth, saved.max_depth);
+        assert_eq!(loaded.shutter, saved.shutter);
+        assert_eq!(loaded.mis_heuristic, saved.mis_heuristic);
+        assert_eq!(loaded.tone_mapping, saved.tone_mapping);
+    }
+
+    #[test]
+    fn damaged_checkpoints_are_rejected() {
+        let path = scratch_file("damaged");
+        checkpoint().save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let mut damaged = Vec::new();
+        let mut not_a_checkpoint = bytes.clone();
+        not_a_checkpoint[0] = b'X';
+        damaged.push(not_a_checkpoint);
+        damaged.push(bytes[..bytes.len() - 1].to_vec());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        damaged.push(trailing);
+        // The sampler id follows the magic, the size, the sample counts and the seed
+        let sampler = MAGIC.len() + 16 + 8;
+        let mut unknown_sampler = bytes.clone();
+        unknown_sampler[sampler] = 200;
+        damaged.push(unknown_sampler);
+        // The filter id and its radius come right after
+        let mut unknown_filter = bytes.clone();
+        unknown_filter[sampler + 1] = 200;
+        damaged.push(unknown_filter);
+        let mut zero_radius = bytes;
+        zero_radius[sampler + 2..sampler + 6].copy_from_slice(&0f32.to_le_bytes());
+        damaged.push(zero_radius);
+
+        for bytes in damaged {
+            fs::write(&path, bytes).unwrap();
+            assert!(Checkpoint::load(&path).is_err());
+        }
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::interval::*;
+use crate::tonemap::ToneMapping;
 use crate::vec3::Vec3;
 
 pub fn linear_to_gamma(linear_component: f32) -> f32 {
@@ -8,19 +8,16 @@ pub fn linear_to_gamma(linear_component: f32) -> f32 {
     0.
 }
 
-pub fn write_color(color: Vec3) -> Vec<u8> {
-    // Apply gamma transformation
-    let r = linear_to_gamma(color.x);
-    let g = linear_to_gamma(color.y);
-    let b = linear_to_gamma(color.z);
+// Perceived brightness of a linear color, with the Rec. 709 weights
+pub fn luminance(color: Vec3) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
 
-    // Transform [0,1] values into byte [0,255] range
-    let intensity = Interval::new(0., 0.999);
-    let rbyte = (256. * intensity.clamp(r)) as u32;
-    let gbyte = (256. * intensity.clamp(g)) as u32;
-    let bbyte = (256. * intensity.clamp(b)) as u32;
+// Formats a linear color as an ASCII PPM pixel using the default tone mapping
+pub fn write_color(color: Vec3) -> Vec<u8> {
+    let [rbyte, gbyte, bbyte] = ToneMapping::default().to_bytes(color);
 
     let color_string = format!("{} {} {}\n", rbyte, gbyte, bbyte);
 
-    return color_string.as_bytes().to_vec();
+    color_string.as_bytes().to_vec()
 }
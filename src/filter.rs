@@ -0,0 +1,282 @@
+use std::f32::consts::PI;
+
+use crate::vec3::Vec3;
+
+// Pixel reconstruction filter weighing every sample by its distance to the centres
+// of the pixels around it. Samples are splatted into every pixel within the radius,
+// in pixels, of the filter, so wider filters blur the image but hide aliasing better.
+// The default box filter covering a single pixel averages its samples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    // Equal weight for every sample within the radius
+    Box { radius: f32 },
+    // Weight falling linearly to zero at the radius
+    Tent { radius: f32 },
+    // Gaussian with the given standard deviation, shifted to reach zero at the
+    // radius. A radius of three standard deviations keeps almost all of it
+    Gaussian { radius: f32, sigma: f32 },
+    // Mitchell-Netravali cubic stretched over the radius. B = C = 1/3 is the usual
+    // balance between blurring and ringing, while lower values of B are sharper
+    Mitchell { radius: f32, b: f32, c: f32 },
+    // Sinc windowed by a wider sinc reaching its first zero at the radius, the
+    // sharpest of the filters and the most prone to ringing
+    Lanczos { radius: f32 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn new_box(radius: f32) -> Self {
+        Self::Box { radius }.validated()
+    }
+
+    pub fn new_tent(radius: f32) -> Self {
+        Self::Tent { radius }.validated()
+    }
+
+    pub fn new_gaussian(radius: f32, sigma: f32) -> Self {
+        Self::Gaussian { radius, sigma }.validated()
+    }
+
+    pub fn new_mitchell(radius: f32, b: f32, c: f32) -> Self {
+        Self::Mitchell { radius, b, c }.validated()
+    }
+
+    pub fn new_lanczos(radius: f32) -> Self {
+        Self::Lanczos { radius }.validated()
+    }
+
+    // Whether the filter can weigh samples at all. Zero radii and standard deviations
+    // divide by zero, and negative ones reach no pixel
+    pub fn is_valid(&self) -> bool {
+        let positive = |value: f32| value > 0. && value.is_finite();
+        match *self {
+            Self::Box { radius } | Self::Tent { radius } | Self::Lanczos { radius } => {
+                positive(radius)
+            }
+            Self::Gaussian { radius, sigma } => positive(radius) && positive(sigma),
+            Self::Mitchell { radius, b, c } => positive(radius) && b.is_finite() && c.is_finite(),
+        }
+    }
+
+    // Panics unless the filter is valid
+    pub(crate) fn validated(self) -> Self {
+        assert!(
+            self.is_valid(),
+            "filter radius and standard deviation must be positive and finite, found {self:?}"
+        );
+        self
+    }
+
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Self::Box { radius }
+            | Self::Tent { radius }
+            | Self::Gaussian { radius, .. }
+            | Self::Mitchell { radius, .. }
+            | Self::Lanczos { radius } => radius,
+        }
+    }
+
+    // Weight of a sample at the given offset in pixels from the centre of a pixel
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        match *self {
+            // Half open, so that a sample on the border of two pixels lands in one
+            // of them only
+            Self::Box { radius } => {
+                if -radius <= x && x < radius {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            Self::Tent { radius } => (1. - x.abs() / radius).max(0.),
+            Self::Gaussian { radius, sigma } => {
+                let gaussian = |x: f32| (-x * x / (2. * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.)
+            }
+            Self::Mitchell { radius, b, c } => mitchell(2. * x.abs() / radius, b, c),
+            Self::Lanczos { radius } => {
+                if x.abs() >= radius {
+                    return 0.;
+                }
+                sinc(x) * sinc(x / radius)
+            }
+        }
+    }
+
+    // Number of pixels on each side of a pixel that its samples reach
+    pub(crate) fn margin(&self) -> u32 {
+        (self.radius() - 0.5).ceil().max(0.) as u32
+    }
+}
+
+// Mitchell-Netravali cubic over [0, 2]
+fn mitchell(x: f32, b: f32, c: f32) -> f32 {
+    let weight = if x < 1. {
+        (12. - 9. * b - 6. * c) * x * x * x + (-18. + 12. * b + 6. * c) * x * x + (6. - 2. * b)
+    } else if x < 2. {
+        (-b - 6. * c) * x * x * x
+            + (6. * b + 30. * c) * x * x
+            + (-12. * b - 48. * c) * x
+            + (8. * b + 24. * c)
+    } else {
+        0.
+    };
+    weight / 6.
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        return 1.;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+// Weighted sums of the samples splatted by one tile of the image into its own pixels
+// and the ones around it that the filter reaches
+pub(crate) struct FilmTile {
+    filter: Filter,
+    pub(crate) x0: u32,
+    pub(crate) y0: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) sums: Vec<Vec3>,
+    pub(crate) weights: Vec<f32>,
+}
+
+impl FilmTile {
+    // Tile for the pixels in [x0, x1) x [y0, y1) of an image of the given size
+    pub(crate) fn new(
+        filter: Filter,
+        (x0, y0, x1, y1): (u32, u32, u32, u32),
+        image_width: u32,
+        image_height: u32,
+    ) -> Self {
+        let margin = filter.margin();
+        let (x0, y0) = (x0.saturating_sub(margin), y0.saturating_sub(margin));
+        let x1 = (x1 + margin).min(image_width);
+        let y1 = (y1 + margin).min(image_height);
+        let size = ((x1 - x0) * (y1 - y0)) as usize;
+
+        Self {
+            filter,
+            x0,
+            y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            sums: vec![Vec3::ZERO; size],
+            weights: vec![0.; size],
+        }
+    }
+
+    // Adds a sample of the pixel (x, y) taken at the given offset from its centre to
+    // every pixel of the tile the filter reaches
+    pub(crate) fn add_sample(&mut self, x: u32, y: u32, offset: (f32, f32), color: Vec3) {
+        let margin = self.filter.margin();
+        let columns =
+            x.saturating_sub(margin).max(self.x0)..(x + margin + 1).min(self.x0 + self.width);
+        let rows =
+            y.saturating_sub(margin).max(self.y0)..(y + margin + 1).min(self.y0 + self.height);
+
+        for row in rows {
+            let dy = y as f32 + offset.1 - row as f32;
+            for column in columns.clone() {
+                let dx = x as f32 + offset.0 - column as f32;
+                let weight = self.filter.evaluate(dx, dy);
+                if weight == 0. {
+                    continue;
+                }
+                let index = ((row - self.y0) * self.width + column - self.x0) as usize;
+                self.sums[index] += weight * color;
+                self.weights[index] += weight;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> [Filter; 5] {
+        [
+            Filter::default(),
+            Filter::new_tent(1.),
+            Filter::new_gaussian(1.5, 0.5),
+            Filter::new_mitchell(2., 1. / 3., 1. / 3.),
+            Filter::new_lanczos(3.),
+        ]
+    }
+
+    #[test]
+    fn weights_peak_at_the_centre_and_vanish_at_the_radius() {
+        for filter in filters() {
+            let radius = filter.radius();
+            let centre = filter.evaluate(0., 0.);
+            assert!(centre > 0., "{filter:?}");
+            for offset in [0.1, 0.3, 0.45] {
+                assert!(filter.evaluate(offset, 0.) <= centre, "{filter:?}");
+                assert_eq!(filter.evaluate(offset, 0.), filter.evaluate(-offset, 0.));
+            }
+            assert!(filter.evaluate(radius, 0.).abs() < 1e-6, "{filter:?}");
+            assert_eq!(filter.evaluate(radius + 0.5, 0.), 0.);
+        }
+    }
+
+    #[test]
+    fn margin_covers_the_pixels_the_filter_reaches() {
+        let margins = filters().map(|filter| filter.margin());
+        assert_eq!(margins, [0, 1, 1, 2, 3]);
+    }
+
+    #[test]
+    fn samples_only_reach_pixels_of_the_tile() {
+        // The tile of the top left pixel grows by the margin inside the image only
+        let mut tile = FilmTile::new(Filter::new_tent(1.5), (0, 0, 1, 1), 4, 4);
+        assert_eq!((tile.x0, tile.y0, tile.width, tile.height), (0, 0, 2, 2));
+
+        tile.add_sample(0, 0, (0.25, 0.), Vec3::splat(1.));
+        assert_eq!(tile.weights.len(), 4);
+        assert!(tile.weights[1] > tile.weights[2]);
+        for (sum, weight) in tile.sums.iter().zip(&tile.weights) {
+            assert_eq!(sum.x, *weight);
+        }
+    }
+
+    #[test]
+    fn degenerate_filters_are_invalid() {
+        let invalid = [
+            Filter::Box { radius: 0. },
+            Filter::Tent { radius: -1. },
+            Filter::Gaussian {
+                radius: 1.,
+                sigma: 0.,
+            },
+            Filter::Mitchell {
+                radius: 2.,
+                b: f32::NAN,
+                c: 0.,
+            },
+            Filter::Lanczos {
+                radius: f32::INFINITY,
+            },
+        ];
+        assert!(filters().iter().all(Filter::is_valid));
+        assert!(!invalid.iter().any(Filter::is_valid));
+    }
+
+    #[test]
+    #[should_panic(expected = "must be positive")]
+    fn constructors_reject_invalid_filters() {
+        Filter::new_gaussian(1., 0.);
+    }
+}
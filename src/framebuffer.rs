@@ -0,0 +1,125 @@
+use std::io;
+use std::path::Path;
+
+use crate::output::OutputFormat;
+use crate::tonemap::ToneMapping;
+use crate::vec3::Vec3;
+
+// Linear, high dynamic range image holding the averaged radiance of every pixel.
+// Pixels are stored row-major starting from the top-left corner of the image
+#[derive(Clone, Debug, Default)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Vec3>,
+}
+
+impl Framebuffer {
+    // Black image of the given size
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::new_from_pixels(width, height, vec![Vec3::ZERO; (width * height) as usize])
+    }
+
+    pub fn new_from_pixels(width: u32, height: u32, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            (width * height) as usize,
+            "pixel count does not match a {width}x{height} image"
+        );
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Vec3 {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Vec3) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    // Tone mapped 8-bit RGB triplets, row-major
+    pub fn to_rgb8(&self, tone_mapping: &ToneMapping) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|color| tone_mapping.to_bytes(*color))
+            .collect()
+    }
+
+    // Writes the image as an ASCII (P3) PPM file after gamma correction and clamping
+    pub fn write_ppm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.save_as(path, OutputFormat::PpmAscii)
+    }
+
+    // Writes the image in the format matching the extension of the path
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let format = OutputFormat::from_path_or_err(path)?;
+        self.save_as(path, format)
+    }
+
+    pub fn save_as(&self, path: impl AsRef<Path>, format: OutputFormat) -> io::Result<()> {
+        self.save_tone_mapped(path, format, &ToneMapping::default())
+    }
+
+    pub fn save_tone_mapped(
+        &self,
+        path: impl AsRef<Path>,
+        format: OutputFormat,
+        tone_mapping: &ToneMapping,
+    ) -> io::Result<()> {
+        format.write(self, tone_mapping, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn pixels_are_row_major() {
+        let mut image = Framebuffer::new(3, 2);
+        image.set(2, 0, Vec3::new(1., 0., 0.));
+        image.set(0, 1, Vec3::new(0., 1., 0.));
+
+        assert_eq!(image.pixels[2].x, 1.);
+        assert_eq!(image.pixels[3].y, 1.);
+        assert_eq!(image.get(0, 1).y, 1.);
+        assert_eq!(image.get(1, 1).y, 0.);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel count does not match a 2x2 image")]
+    fn pixel_count_must_match_the_size() {
+        Framebuffer::new_from_pixels(2, 2, vec![Vec3::ZERO; 3]);
+    }
+
+    #[test]
+    fn ppm_holds_every_pixel() {
+        let image = Framebuffer::new_from_pixels(
+            2,
+            1,
+            vec![Vec3::new(1., 0., 0.25), Vec3::new(4., 0., 0.)],
+        );
+        let path = env::temp_dir().join(format!("framebuffer-{}.ppm", std::process::id()));
+
+        image.write_ppm(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        // Gamma corrected with a square root and clamped below 256
+        assert_eq!(contents, "P3\n2 1\n255\n255 0 128\n255 0 0\n");
+    }
+}
@@ -1,12 +1,15 @@
 use core::f32;
 use std::f32::consts::PI;
+use std::sync::Arc;
 
 use crate::aabb::Aabb;
 use crate::interval::*;
 use crate::material::Material;
+use crate::matrix::Transform;
+use crate::onb::Onb;
 use crate::quad::Quad;
 use crate::ray::*;
-use crate::utilities::degrees_to_radians;
+use crate::utilities::{degrees_to_radians, pick_with_sample, RenderRng};
 use crate::vec3::*;
 
 #[derive(Clone)]
@@ -45,16 +48,63 @@ impl HitData {
     }
 }
 
-pub trait Hittable {
-    fn hit(&self, ray: Ray, interval: Interval, hit_data: &mut HitData) -> bool;
+pub trait Hittable: Send + Sync {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool;
 
     fn bounding_box(&self) -> Aabb;
+
+    // Probability density, with respect to solid angle, that `random` picks the
+    // direction of the ray when called from its origin at its time. Objects that
+    // cannot be sampled return zero
+    fn pdf_value(&self, _ray: Ray, _rng: &mut RenderRng) -> f32 {
+        0.
+    }
+
+    // Direction from the origin towards a point of the object at the given time, not
+    // necessarily of unit length, picked with a sample point of the unit square
+    fn random(&self, _origin: Vec3, _time: f32, _sample: (f32, f32)) -> Vec3 {
+        Vec3::new(1., 0., 0.)
+    }
+}
+
+// Lets geometry shared between several instances be used wherever a hittable is
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
+        (**self).hit(ray, interval, hit_data, rng)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        (**self).pdf_value(ray, rng)
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        (**self).random(origin, time, sample)
+    }
 }
 
 #[derive(Default)]
 pub struct HittableList {
     pub objects: Vec<Box<dyn Hittable>>,
     pub bbox: Aabb,
+    // Emitters sampled directly when lighting diffuse surfaces. Lights only count
+    // when registered on the list the camera renders
+    pub lights: Vec<Arc<dyn Hittable>>,
 }
 
 impl HittableList {
@@ -63,12 +113,55 @@ impl HittableList {
         self.objects.push(object);
     }
 
+    // Adds an emissive object to the scene and registers it for light sampling
+    pub fn add_light(&mut self, light: Arc<dyn Hittable>) {
+        self.add(Box::new(light.clone()));
+        self.lights.push(light);
+    }
+
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.lights.clear();
         self.bbox = Aabb::default();
     }
 
-    pub fn hit(&self, ray: Ray, interval: Interval, hit_data: &mut HitData) -> bool {
+    // Density of sampling the direction of the ray by picking one of the registered
+    // lights uniformly and sampling a direction towards it
+    pub fn light_pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        if self.lights.is_empty() {
+            return 0.;
+        }
+        let weight = 1. / self.lights.len() as f32;
+        self.lights
+            .iter()
+            .map(|light| weight * light.pdf_value(ray, rng))
+            .sum()
+    }
+
+    // Direction from the origin towards one of the registered lights, or None when
+    // there are no lights
+    pub fn random_light_direction(
+        &self,
+        origin: Vec3,
+        time: f32,
+        sample: (f32, f32),
+    ) -> Option<Vec3> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let (index, sample) = pick_with_sample(sample, self.lights.len());
+        Some(self.lights[index].random(origin, time, sample))
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
         let mut hit_anything = false;
         let mut closest_hit = interval.max;
 
@@ -80,6 +173,7 @@ impl HittableList {
                 ray,
                 Interval::new(interval.min, closest_hit),
                 &mut temp_hit_data,
+                rng,
             ) {
                 hit_anything = true;
                 closest_hit = temp_hit_data.hit_along_ray;
@@ -89,6 +183,30 @@ impl HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Mixture picking each object of the list with the same probability
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        if self.objects.is_empty() {
+            return 0.;
+        }
+        let weight = 1. / self.objects.len() as f32;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(ray, rng))
+            .sum()
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1., 0., 0.);
+        }
+        let (index, sample) = pick_with_sample(sample, self.objects.len());
+        self.objects[index].random(origin, time, sample)
+    }
 }
 
 pub struct Sphere {
@@ -132,7 +250,13 @@ impl Sphere {
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: Ray, interval: Interval, hit_data: &mut HitData) -> bool {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        _rng: &mut RenderRng,
+    ) -> bool {
         let center = self.sphere_center(ray.time);
         let origin_gap = center - ray.origin;
         // Quadratic constants for solving the ray intersection equation
@@ -167,6 +291,36 @@ impl Hittable for Sphere {
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
+
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        let mut hit_data = HitData::default();
+        if !self.hit(ray, Interval::new(0.001, f32::INFINITY), &mut hit_data, rng) {
+            return 0.;
+        }
+
+        // Directions are drawn uniformly from the cone around the sphere, or from all
+        // directions when the origin is inside it
+        let distance_squared = (self.sphere_center(ray.time) - ray.origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1. / (4. * PI);
+        }
+        let cos_theta_max = (1. - radius_squared / distance_squared).sqrt();
+        1. / (2. * PI * (1. - cos_theta_max))
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        let direction = self.sphere_center(time) - origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return Vec3::uniform_sphere(sample);
+        }
+
+        // Uniform direction within the cone around the axis towards the center
+        let cos_theta_max = (1. - radius_squared / distance_squared).sqrt();
+        Onb::new(direction).to_world(Vec3::uniform_cone(sample, cos_theta_max))
+    }
 }
 
 pub struct BoxObject {
@@ -233,13 +387,27 @@ impl BoxObject {
 }
 
 impl Hittable for BoxObject {
-    fn hit(&self, ray: Ray, interval: Interval, hit_data: &mut HitData) -> bool {
-        self.sides.hit(ray, interval, hit_data)
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
+        self.sides.hit(ray, interval, hit_data, rng)
     }
 
     fn bounding_box(&self) -> Aabb {
         self.sides.bbox
     }
+
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        self.sides.pdf_value(ray, rng)
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        self.sides.random(origin, time, sample)
+    }
 }
 
 pub struct TranslateInstance {
@@ -261,10 +429,16 @@ impl TranslateInstance {
 }
 
 impl Hittable for TranslateInstance {
-    fn hit(&self, ray: Ray, interval: Interval, hit_data: &mut HitData) -> bool {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
         let offset_ray = Ray::new(ray.origin - self.offset, ray.direction, ray.time);
 
-        if !self.object.hit(offset_ray, interval, hit_data) {
+        if !self.object.hit(offset_ray, interval, hit_data, rng) {
             return false;
         };
 
@@ -275,11 +449,19 @@ impl Hittable for TranslateInstance {
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
+
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        let offset_ray = Ray::new(ray.origin - self.offset, ray.direction, ray.time);
+        self.object.pdf_value(offset_ray, rng)
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        self.object.random(origin - self.offset, time, sample)
+    }
 }
 
 pub struct YRotationInstance {
     object: Box<dyn Hittable>,
-    theta: f32,
     sin_theta: f32,
     cos_theta: f32,
     bbox: Aabb,
@@ -292,8 +474,8 @@ impl YRotationInstance {
         let cos_theta = radians.cos();
 
         let mut bbox = object.bounding_box();
-        let mut box_corner_1 = Vec3::ZERO;
-        let mut box_corner_2 = Vec3::ZERO;
+        let mut box_corner_1 = Vec3::splat(f32::INFINITY);
+        let mut box_corner_2 = Vec3::splat(f32::NEG_INFINITY);
 
         for i in 0..2 {
             for j in 0..2 {
@@ -308,8 +490,8 @@ impl YRotationInstance {
                     let temp = Vec3::new(new_x, y, new_z);
 
                     for c in Dim::ALL {
-                        box_corner_1.set(c, f32::min(f32::INFINITY, temp.get(c)));
-                        box_corner_2.set(c, f32::max(f32::NEG_INFINITY, temp.get(c)));
+                        box_corner_1.set(c, f32::min(box_corner_1.get(c), temp.get(c)));
+                        box_corner_2.set(c, f32::max(box_corner_2.get(c), temp.get(c)));
                     }
                 }
             }
@@ -319,51 +501,320 @@ impl YRotationInstance {
 
         Self {
             object,
-            theta,
             sin_theta,
             cos_theta,
             bbox,
         }
     }
+
+    // Rotates a point or direction of the scene into the space of the object
+    fn to_object(&self, vec: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * vec.x - self.sin_theta * vec.z,
+            vec.y,
+            self.sin_theta * vec.x + self.cos_theta * vec.z,
+        )
+    }
+
+    fn to_world(&self, vec: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * vec.x + self.sin_theta * vec.z,
+            vec.y,
+            -self.sin_theta * vec.x + self.cos_theta * vec.z,
+        )
+    }
 }
 
 impl Hittable for YRotationInstance {
-    fn hit(&self, ray: Ray, interval: Interval, hit_data: &mut HitData) -> bool {
-        // COnstruct transformed ray
-        let origin = Vec3::new(
-            self.cos_theta * ray.origin.x - self.sin_theta * ray.origin.z,
-            ray.origin.y,
-            self.sin_theta * ray.origin.x + self.cos_theta * ray.origin.z,
-        );
-        let direction = Vec3::new(
-            self.cos_theta * ray.direction.x - self.sin_theta * ray.direction.z,
-            ray.direction.y,
-            self.sin_theta * ray.direction.x + self.cos_theta * ray.direction.z,
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
+        // Construct transformed ray
+        let rotated_ray = Ray::new(
+            self.to_object(ray.origin),
+            self.to_object(ray.direction),
+            ray.time,
         );
 
-        let rotated_ray = Ray::new(origin, direction, ray.time);
-
         // Check intersection of transformed ray with the object
-        if !self.object.hit(rotated_ray, interval, hit_data) {
+        if !self.object.hit(rotated_ray, interval, hit_data, rng) {
             return false;
         };
 
         // Rotate the hit data point and normal back appropriately
-        hit_data.point = Vec3::new(
-            self.cos_theta * hit_data.point.x + self.sin_theta * hit_data.point.z,
-            hit_data.point.y,
-            -self.sin_theta * hit_data.point.x + self.cos_theta * hit_data.point.z,
-        );
-        hit_data.normal = Vec3::new(
-            self.cos_theta * hit_data.normal.x + self.sin_theta * hit_data.normal.z,
-            hit_data.normal.y,
-            -self.sin_theta * hit_data.normal.x + self.cos_theta * hit_data.normal.z,
+        hit_data.point = self.to_world(hit_data.point);
+        hit_data.normal = self.to_world(hit_data.normal);
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Rotations preserve solid angles, so densities carry over unchanged
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        let rotated_ray = Ray::new(
+            self.to_object(ray.origin),
+            self.to_object(ray.direction),
+            ray.time,
         );
+        self.object.pdf_value(rotated_ray, rng)
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        let direction = self.object.random(self.to_object(origin), time, sample);
+        self.to_world(direction)
+    }
+}
+
+// Object placed in the scene by any affine transformation, such as a composition of
+// translations, rotations, scalings and shears
+pub struct TransformInstance {
+    object: Box<dyn Hittable>,
+    transform: Transform,
+    bbox: Aabb,
+}
+
+impl TransformInstance {
+    pub fn new(object: Box<dyn Hittable>, transform: Transform) -> TransformInstance {
+        let bbox = transform.bounding_box(object.bounding_box());
 
+        Self {
+            object,
+            transform,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for TransformInstance {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
+        if !self
+            .object
+            .hit(self.transform.ray_to_local(ray), interval, hit_data, rng)
+        {
+            return false;
+        };
+
+        hit_data.point = self.transform.point(hit_data.point);
+        hit_data.normal = self.transform.normal(hit_data.normal).unit();
         true
     }
 
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
+
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        let local_pdf = self.object.pdf_value(self.transform.ray_to_local(ray), rng);
+        local_pdf * self.transform.direction_density(ray.direction)
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        let local_origin = self.transform.inverse().point(origin);
+        self.transform
+            .vector(self.object.random(local_origin, time, sample))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::utilities::{random_num, seeded_rng};
+
+    fn hit(object: &dyn Hittable, ray: Ray) -> Option<HitData> {
+        let mut hit_data = HitData::default();
+        let interval = Interval::new(0.001, f32::INFINITY);
+        object
+            .hit(ray, interval, &mut hit_data, &mut seeded_rng(0))
+            .then_some(hit_data)
+    }
+
+    #[test]
+    fn y_rotation_bounds_every_rotated_corner() {
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::splat(0.5)));
+        let object = BoxObject::new(Vec3::new(1., 0., 1.), Vec3::new(3., 1., 2.), material);
+        let bbox = YRotationInstance::new(Box::new(object), 90.).bounding_box();
+
+        // A quarter turn maps (x, z) onto (z, -x). The faces of the box are padded
+        // by a tiny margin, so the bounds are only compared approximately
+        assert!((bbox.x.min - 1.).abs() < 1e-3 && (bbox.x.max - 2.).abs() < 1e-3);
+        assert!((bbox.z.min + 3.).abs() < 1e-3 && (bbox.z.max + 1.).abs() < 1e-3);
+        assert!(bbox.y.min <= 0. && bbox.y.max >= 1.);
+    }
+
+    #[test]
+    fn transform_instance_places_the_object() {
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::splat(0.5)));
+        let sphere = Sphere::new(Vec3::ZERO, Vec3::ZERO, 1., material);
+        let transform = Transform::identity()
+            .scale(Vec3::new(2., 1., 1.))
+            .translate(Vec3::new(0., 0., -5.));
+        let instance = TransformInstance::new(Box::new(sphere), transform);
+
+        let bbox = instance.bounding_box();
+        assert!((bbox.x.min + 2.).abs() < 1e-4 && (bbox.x.max - 2.).abs() < 1e-4);
+        assert!((bbox.z.min + 6.).abs() < 1e-4 && (bbox.z.max + 4.).abs() < 1e-4);
+
+        let along_x = Ray::new(Vec3::new(5., 0., -5.), Vec3::new(-1., 0., 0.), 0.);
+        let hit_data = hit(&instance, along_x).expect("ray should hit the ellipsoid");
+        assert!((hit_data.hit_along_ray - 3.).abs() < 1e-4);
+        assert!((hit_data.point - Vec3::new(2., 0., -5.)).length() < 1e-4);
+        assert!((hit_data.normal - Vec3::new(1., 0., 0.)).length() < 1e-4);
+
+        let past_the_end = Ray::new(Vec3::new(2.5, 0., 0.), Vec3::new(0., 0., -1.), 0.);
+        assert!(hit(&instance, past_the_end).is_none());
+    }
+
+    // Checks that `random` and `pdf_value` describe the same distribution of
+    // directions seen from the origin, which both have to cover the same solid angle:
+    // integrating the density over the sphere of directions gives one, and the mean
+    // of the reciprocal density of sampled directions gives the covered solid angle
+    pub(crate) fn assert_sampling_is_consistent(
+        object: &dyn Hittable,
+        origin: Vec3,
+        solid_angle: f32,
+    ) {
+        let mut rng = seeded_rng(3);
+        let count = 100_000;
+
+        let mut integral = 0.;
+        for _ in 0..count {
+            let direction = Vec3::random_unit_vector(&mut rng);
+            integral += object.pdf_value(Ray::new(origin, direction, 0.), &mut rng);
+        }
+        let integral = 4. * PI * integral / count as f32;
+        assert!(
+            (integral - 1.).abs() < 0.05,
+            "density integrates to {integral}"
+        );
+
+        let mut covered = 0.;
+        for _ in 0..count {
+            let direction = object.random(origin, 0., (random_num(&mut rng), random_num(&mut rng)));
+            let pdf = object.pdf_value(Ray::new(origin, direction, 0.), &mut rng);
+            assert!(pdf > 0., "sampled direction {direction:?} has no density");
+            covered += 1. / pdf;
+        }
+        let covered = covered / count as f32;
+        assert!(
+            (covered - solid_angle).abs() < 0.01 * solid_angle,
+            "samples cover {covered} instead of {solid_angle}"
+        );
+    }
+
+    fn gray() -> Material {
+        Material::Lambertian(Lambertian::new_from_color(Vec3::splat(0.5)))
+    }
+
+    #[test]
+    fn sphere_sampling_matches_its_density() {
+        let center = Vec3::new(0., 0., -3.);
+        let sphere = Sphere::new(center, center, 1., gray());
+        // Cone of half angle asin(1/3) around the direction to the center
+        let cos_theta_max = (8f32 / 9.).sqrt();
+        assert_sampling_is_consistent(&sphere, Vec3::ZERO, 2. * PI * (1. - cos_theta_max));
+    }
+
+    #[test]
+    fn quad_sampling_matches_its_density() {
+        // The square covers one face of the cube around the origin
+        assert_sampling_is_consistent(&face_of_cube(), Vec3::ZERO, 4. * PI / 6.);
+    }
+
+    fn face_of_cube() -> Quad {
+        Quad::new(
+            Vec3::new(-1., -1., -1.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(0., 2., 0.),
+            gray(),
+        )
+    }
+
+    // Solid angle of a square of the given half size seen from a point at the given
+    // distance above its center
+    fn square_solid_angle(half_size: f32, distance: f32) -> f32 {
+        let a = half_size * half_size;
+        let d = distance * distance;
+        4. * (a / (a + d)).asin()
+    }
+
+    #[test]
+    fn box_sampling_matches_its_density() {
+        let object = BoxObject::new(Vec3::new(-1., -1., -4.), Vec3::new(1., 1., -2.), gray());
+        // Seen along its axis the box hides everything behind its front face
+        assert_sampling_is_consistent(&object, Vec3::ZERO, square_solid_angle(1., 2.));
+    }
+
+    #[test]
+    fn instance_sampling_matches_its_density() {
+        let offset = Vec3::new(3., -2., 5.);
+        let translated = TranslateInstance::new(Box::new(face_of_cube()), offset);
+        assert_sampling_is_consistent(&translated, offset, 4. * PI / 6.);
+
+        let rotated = YRotationInstance::new(Box::new(face_of_cube()), 30.);
+        assert_sampling_is_consistent(&rotated, Vec3::ZERO, 4. * PI / 6.);
+
+        // Stretching along the view direction moves the face twice as far away,
+        // which is only covered if the density accounts for the distortion
+        let transform = Transform::identity()
+            .scale(Vec3::new(1., 1., 2.))
+            .rotate_y(45.);
+        let transformed = TransformInstance::new(Box::new(face_of_cube()), transform);
+        assert_sampling_is_consistent(&transformed, Vec3::ZERO, square_solid_angle(1., 2.));
+    }
+
+    #[test]
+    fn light_density_mixes_every_registered_light() {
+        let mut world = HittableList::default();
+        let mut rng = seeded_rng(5);
+        assert!(world
+            .random_light_direction(Vec3::ZERO, 0., (random_num(&mut rng), random_num(&mut rng)))
+            .is_none());
+
+        let center = Vec3::new(0., 0., -3.);
+        world.add_light(Arc::new(Sphere::new(center, center, 1., gray())));
+        world.add_light(Arc::new(Quad::new(
+            Vec3::new(-1., 2., -1.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(0., 0., 2.),
+            gray(),
+        )));
+        assert_eq!(world.objects.len(), 2);
+
+        let count = 100_000;
+        let mut integral = 0.;
+        for _ in 0..count {
+            let direction = Vec3::random_unit_vector(&mut rng);
+            integral += world.light_pdf_value(Ray::new(Vec3::ZERO, direction, 0.), &mut rng);
+        }
+        let integral = 4. * PI * integral / count as f32;
+        assert!(
+            (integral - 1.).abs() < 0.05,
+            "density integrates to {integral}"
+        );
+
+        for _ in 0..1000 {
+            let direction = world
+                .random_light_direction(
+                    Vec3::ZERO,
+                    0.,
+                    (random_num(&mut rng), random_num(&mut rng)),
+                )
+                .unwrap();
+            assert!(world.light_pdf_value(Ray::new(Vec3::ZERO, direction, 0.), &mut rng) > 0.);
+        }
+    }
 }
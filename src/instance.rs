@@ -0,0 +1,359 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitData, Hittable},
+    interval::Interval,
+    material::Material,
+    matrix::{AnimatedTransform, Transform},
+    ray::Ray,
+    utilities::RenderRng,
+    vec3::Vec3,
+};
+
+// Placement of shared geometry in the scene. The geometry, usually a mesh or a
+// bounding volume hierarchy of its own, is stored once behind an Arc while every
+// instance only carries its transformation and an optional material replacing the
+// ones of the geometry. Instances are meant to be gathered into a top level
+// bounding volume hierarchy
+pub struct Instance {
+    geometry: Arc<dyn Hittable>,
+    local_transform: Transform, // Applied to the geometry before the placement below
+    y_rotation: f32,
+    offset: Vec3,
+    transform: Transform, // Everything above combined, from geometry to scene space
+    material: Option<Material>,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn new(geometry: Arc<dyn Hittable>) -> Self {
+        let bbox = geometry.bounding_box();
+
+        Self {
+            geometry,
+            local_transform: Transform::identity(),
+            y_rotation: 0.,
+            offset: Vec3::ZERO,
+            transform: Transform::identity(),
+            material: None,
+            bbox,
+        }
+    }
+
+    // Rotation in degrees about the Y axis, applied before the translation
+    pub fn with_y_rotation(mut self, theta: f32) -> Self {
+        self.y_rotation = theta;
+        self.update_transform()
+    }
+
+    pub fn with_translation(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self.update_transform()
+    }
+
+    // General transformation of the geometry, such as a scaling or shear, applied
+    // before the rotation and translation above. Steps are applied in the order they
+    // are chained onto the transformation, and a later call replaces an earlier one
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.local_transform = transform;
+        self.update_transform()
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    fn update_transform(mut self) -> Self {
+        self.transform = self
+            .local_transform
+            .rotate_y(self.y_rotation)
+            .translate(self.offset);
+        self.bbox = self.transform.bounding_box(self.geometry.bounding_box());
+        self
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
+        let object_ray = self.transform.ray_to_local(ray);
+        if !self.geometry.hit(object_ray, interval, hit_data, rng) {
+            return false;
+        }
+
+        hit_data.point = self.transform.point(hit_data.point);
+        hit_data.normal = self.transform.normal(hit_data.normal).unit();
+        if let Some(material) = &self.material {
+            hit_data.material = Some(material.clone());
+        }
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        let local_pdf = self
+            .geometry
+            .pdf_value(self.transform.ray_to_local(ray), rng);
+        local_pdf * self.transform.direction_density(ray.direction)
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        let local_origin = self.transform.inverse().point(origin);
+        self.transform
+            .vector(self.geometry.random(local_origin, time, sample))
+    }
+}
+
+// Shared geometry moving over the shutter interval. The transformation for every
+// ray is interpolated from the keyframes at the time of the ray, so any object can
+// be motion blurred, and the bounding box covers the whole motion
+pub struct AnimatedInstance {
+    geometry: Arc<dyn Hittable>,
+    animation: AnimatedTransform,
+    material: Option<Material>,
+    bbox: Aabb,
+}
+
+impl AnimatedInstance {
+    pub fn new(geometry: Arc<dyn Hittable>, animation: AnimatedTransform) -> Self {
+        let bbox = animation.bounding_box(geometry.bounding_box());
+
+        Self {
+            geometry,
+            animation,
+            material: None,
+            bbox,
+        }
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl Hittable for AnimatedInstance {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        rng: &mut RenderRng,
+    ) -> bool {
+        // A pose that cannot be inverted has no surface to hit
+        let Some(transform) = self.animation.at(ray.time) else {
+            return false;
+        };
+        if !self
+            .geometry
+            .hit(transform.ray_to_local(ray), interval, hit_data, rng)
+        {
+            return false;
+        }
+
+        hit_data.point = transform.point(hit_data.point);
+        hit_data.normal = transform.normal(hit_data.normal).unit();
+        if let Some(material) = &self.material {
+            hit_data.material = Some(material.clone());
+        }
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        let Some(transform) = self.animation.at(ray.time) else {
+            return 0.;
+        };
+        let local_pdf = self.geometry.pdf_value(transform.ray_to_local(ray), rng);
+        local_pdf * transform.direction_density(ray.direction)
+    }
+
+    fn random(&self, origin: Vec3, time: f32, sample: (f32, f32)) -> Vec3 {
+        let Some(transform) = self.animation.at(time) else {
+            return Vec3::new(1., 0., 0.);
+        };
+        let local_origin = transform.inverse().point(origin);
+        transform.vector(self.geometry.random(local_origin, time, sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::assert_sampling_is_consistent;
+    use crate::hittable::{BoxObject, Sphere};
+    use crate::material::Lambertian;
+    use crate::matrix::Keyframe;
+    use crate::utilities::seeded_rng;
+    use std::f32::consts::PI;
+
+    fn material(gray: f32) -> Material {
+        Material::Lambertian(Lambertian::new_from_color(Vec3::splat(gray)))
+    }
+
+    fn hit(object: &dyn Hittable, ray: Ray) -> Option<HitData> {
+        let mut hit_data = HitData::default();
+        let interval = Interval::new(0.001, f32::INFINITY);
+        object
+            .hit(ray, interval, &mut hit_data, &mut seeded_rng(0))
+            .then_some(hit_data)
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{a:?} is not {b:?}");
+    }
+
+    #[test]
+    fn instances_share_one_geometry() {
+        let sphere: Arc<dyn Hittable> =
+            Arc::new(Sphere::new(Vec3::ZERO, Vec3::ZERO, 1., material(0.5)));
+        let left = Instance::new(sphere.clone()).with_translation(Vec3::new(-3., 0., 0.));
+        let right = Instance::new(sphere.clone()).with_translation(Vec3::new(3., 0., 0.));
+        assert_eq!(Arc::strong_count(&sphere), 3);
+
+        for (instance, x) in [(&left, -3.), (&right, 3.)] {
+            let ray = Ray::new(Vec3::new(x, 0., 5.), Vec3::new(0., 0., -1.), 0.);
+            let hit_data = hit(instance, ray).expect("ray should hit the instance");
+            assert!((hit_data.hit_along_ray - 4.).abs() < 1e-4);
+            assert_close(hit_data.point, Vec3::new(x, 0., 1.));
+            assert_close(hit_data.normal, Vec3::new(0., 0., 1.));
+
+            let beside = Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., -1.), 0.);
+            assert!(hit(instance, beside).is_none());
+        }
+    }
+
+    #[test]
+    fn rotation_applies_before_translation_in_any_call_order() {
+        let geometry: Arc<dyn Hittable> = Arc::new(BoxObject::new(
+            Vec3::ZERO,
+            Vec3::new(2., 1., 0.5),
+            material(0.5),
+        ));
+        let offset = Vec3::new(10., 0., 0.);
+        let rotated_first = Instance::new(geometry.clone())
+            .with_y_rotation(90.)
+            .with_translation(offset);
+        let translated_first = Instance::new(geometry)
+            .with_translation(offset)
+            .with_y_rotation(90.);
+
+        // A quarter turn maps x onto -z, so the box spans x in [0, 0.5] and z in [-2, 0]
+        // before being moved along x
+        for instance in [&rotated_first, &translated_first] {
+            let ray = Ray::new(Vec3::new(10.25, 0.5, 5.), Vec3::new(0., 0., -1.), 0.);
+            let hit_data = hit(instance, ray).expect("ray should hit the instance");
+            assert!((hit_data.hit_along_ray - 5.).abs() < 1e-4);
+            assert_close(hit_data.normal, Vec3::new(0., 0., 1.));
+
+            let bbox = instance.bounding_box();
+            assert!((bbox.x.min - 10.).abs() < 1e-4 && (bbox.x.max - 10.5).abs() < 1e-4);
+            assert!((bbox.z.min + 2.).abs() < 1e-4 && bbox.z.max.abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn general_transform_applies_before_the_placement() {
+        let sphere: Arc<dyn Hittable> =
+            Arc::new(Sphere::new(Vec3::ZERO, Vec3::ZERO, 1., material(0.5)));
+        let stretch = Transform::identity().scale(Vec3::new(1., 1., 3.));
+        let placements = [
+            Instance::new(sphere.clone())
+                .with_transform(stretch)
+                .with_y_rotation(90.)
+                .with_translation(Vec3::new(0., 0., -10.)),
+            Instance::new(sphere)
+                .with_translation(Vec3::new(0., 0., -10.))
+                .with_y_rotation(90.)
+                .with_transform(stretch),
+        ];
+
+        // Stretched along z, then turned so that the long axis lies along x
+        for instance in placements {
+            let bbox = instance.bounding_box();
+            assert!((bbox.x.min + 3.).abs() < 1e-4 && (bbox.x.max - 3.).abs() < 1e-4);
+            assert!((bbox.z.min + 11.).abs() < 1e-4 && (bbox.z.max + 9.).abs() < 1e-4);
+
+            let ray = Ray::new(Vec3::new(5., 0., -10.), Vec3::new(-1., 0., 0.), 0.);
+            let hit_data = hit(&instance, ray).expect("ray should hit the instance");
+            assert!((hit_data.hit_along_ray - 2.).abs() < 1e-4);
+            assert_close(hit_data.normal, Vec3::new(1., 0., 0.));
+        }
+    }
+
+    #[test]
+    fn material_override_replaces_the_geometry_material() {
+        let sphere: Arc<dyn Hittable> =
+            Arc::new(Sphere::new(Vec3::ZERO, Vec3::ZERO, 1., material(0.5)));
+        let ray = Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., -1.), 0.);
+
+        let plain = hit(&Instance::new(sphere.clone()), ray).unwrap();
+        let overridden = hit(&Instance::new(sphere).with_material(material(0.9)), ray).unwrap();
+        let albedo = |hit_data: &HitData| match &hit_data.material {
+            Some(Material::Lambertian(lambertian)) => lambertian.texture.value(0., 0., Vec3::ZERO),
+            _ => panic!("expected a diffuse material"),
+        };
+        assert_close(albedo(&plain), Vec3::splat(0.5));
+        assert_close(albedo(&overridden), Vec3::splat(0.9));
+    }
+    #[test]
+    fn animated_instance_follows_the_ray_time() {
+        let sphere: Arc<dyn Hittable> =
+            Arc::new(Sphere::new(Vec3::ZERO, Vec3::ZERO, 1., material(0.5)));
+        let animation = AnimatedTransform::new()
+            .keyframe(0., Keyframe::new(Vec3::new(-2., 0., -5.)))
+            .keyframe(1., Keyframe::new(Vec3::new(2., 0., -5.)));
+        let instance = AnimatedInstance::new(sphere, animation);
+
+        for (time, x) in [(0., -2.), (0.5, 0.), (1., 2.)] {
+            let ray = Ray::new(Vec3::new(x, 0., 0.), Vec3::new(0., 0., -1.), time);
+            let hit_data = hit(&instance, ray).expect("ray should hit the moving sphere");
+            assert!((hit_data.hit_along_ray - 4.).abs() < 1e-4);
+            assert_close(hit_data.normal, Vec3::new(0., 0., 1.));
+        }
+        let early = Ray::new(Vec3::new(2., 0., 0.), Vec3::new(0., 0., -1.), 0.);
+        assert!(hit(&instance, early).is_none());
+
+        let bbox = instance.bounding_box();
+        assert!(bbox.x.min <= -3. && bbox.x.max >= 3.);
+    }
+
+    #[test]
+    fn sampling_matches_the_density() {
+        // Unit sphere squashed along the view direction and placed five units away.
+        // Seen from the origin, ten local units from its center, the tangent cone has
+        // a sine of 0.1 before the squash, and the squash widens it
+        let sphere: Arc<dyn Hittable> =
+            Arc::new(Sphere::new(Vec3::ZERO, Vec3::ZERO, 1., material(0.5)));
+        let tan_theta = 2. * 0.1 / (0.5 * 0.99f32.sqrt());
+        let cos_theta = 1. / (1. + tan_theta * tan_theta).sqrt();
+        let instance = Instance::new(sphere.clone())
+            .with_transform(Transform::identity().scale(Vec3::new(2., 2., 0.5)))
+            .with_translation(Vec3::new(0., 0., -5.));
+        assert_sampling_is_consistent(&instance, Vec3::ZERO, 2. * PI * (1. - cos_theta));
+
+        // Uniformly scaled it is a sphere of radius two
+        let keyframe = Keyframe::new(Vec3::new(0., 0., -5.)).with_scale(Vec3::splat(2.));
+        let animation = AnimatedTransform::new()
+            .keyframe(0., keyframe)
+            .keyframe(1., keyframe);
+        let animated = AnimatedInstance::new(sphere, animation);
+        let cos_theta = (1f32 - 4. / 25.).sqrt();
+        assert_sampling_is_consistent(&animated, Vec3::ZERO, 2. * PI * (1. - cos_theta));
+    }
+}
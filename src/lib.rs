@@ -1,14 +1,30 @@
 pub mod aabb;
+pub mod adaptive;
 pub mod bvh;
 pub mod camera;
+pub mod checkpoint;
 pub mod color;
+pub mod filter;
+pub mod framebuffer;
 pub mod hittable;
+pub mod instance;
 pub mod interval;
 pub mod material;
+pub mod matrix;
+pub mod mesh;
+pub mod obj;
+pub mod onb;
+pub mod output;
 pub mod perlin;
+pub mod progress;
+pub mod progressive;
 pub mod quad;
 pub mod ray;
+pub mod sampler;
+pub mod shutter;
 pub mod texture;
+pub mod tonemap;
+pub mod triangle;
 pub mod utilities;
 pub mod vec3;
 pub mod volume;
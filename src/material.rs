@@ -1,8 +1,10 @@
+use std::f32::consts::PI;
+
 use crate::{
     hittable::HitData,
+    onb::Onb,
     ray::Ray,
     texture::{SolidTexture, Texture},
-    utilities::random_num,
     vec3::Vec3,
 };
 
@@ -22,26 +24,64 @@ impl Default for Material {
 }
 
 impl Material {
+    // The sample point of the unit square drives the random choices of the scattering,
+    // such as the direction of diffuse and glossy rays
     pub fn scatter(
         &self,
         ray_in: Ray,
         hit_data: &mut HitData,
         attenuation: &mut Vec3,
         scattered: &mut Ray,
+        sample: (f32, f32),
     ) -> bool {
         match self {
-            Self::Lambertian(lamb) => lamb.scatter(ray_in, hit_data, attenuation, scattered),
-            Self::Metal(metal) => metal.scatter(ray_in, hit_data, attenuation, scattered),
+            Self::Lambertian(lamb) => {
+                lamb.scatter(ray_in, hit_data, attenuation, scattered, sample)
+            }
+            Self::Metal(metal) => metal.scatter(ray_in, hit_data, attenuation, scattered, sample),
             Self::Dielectric(dielectric) => {
-                dielectric.scatter(ray_in, hit_data, attenuation, scattered)
+                dielectric.scatter(ray_in, hit_data, attenuation, scattered, sample)
             }
             Self::DiffuseLight(_) => false,
             Self::Isotropic(isotropic) => {
-                isotropic.scatter(ray_in, hit_data, attenuation, scattered)
+                isotropic.scatter(ray_in, hit_data, attenuation, scattered, sample)
             }
         }
     }
 
+    // Whether the material scatters into a single direction, like a mirror or glass.
+    // Such materials reflect nothing from a direction chosen in advance, so their
+    // lighting can only be found by following the scattered ray
+    pub fn is_specular(&self) -> bool {
+        match self {
+            Self::Metal(metal) => metal.fuzz <= 0.,
+            Self::Dielectric(_) | Self::DiffuseLight(_) => true,
+            Self::Lambertian(_) | Self::Isotropic(_) => false,
+        }
+    }
+
+    // Fraction of the light arriving from the direction that is reflected back along
+    // the incoming ray, including the cosine of the angle to the normal for surfaces
+    pub fn eval(&self, ray_in: Ray, hit_data: &HitData, direction: Vec3) -> Vec3 {
+        match self {
+            Self::Lambertian(lamb) => lamb.eval(ray_in, hit_data, direction),
+            Self::Metal(metal) => metal.eval(ray_in, hit_data, direction),
+            Self::Isotropic(isotropic) => isotropic.eval(ray_in, hit_data, direction),
+            Self::Dielectric(_) | Self::DiffuseLight(_) => Vec3::ZERO,
+        }
+    }
+
+    // Density per solid angle with which `scatter` picks the direction. The
+    // attenuation of a scattered ray is the ratio of `eval` to this density
+    pub fn pdf(&self, ray_in: Ray, hit_data: &HitData, direction: Vec3) -> f32 {
+        match self {
+            Self::Lambertian(lamb) => lamb.pdf(ray_in, hit_data, direction),
+            Self::Metal(metal) => metal.pdf(ray_in, hit_data, direction),
+            Self::Isotropic(isotropic) => isotropic.pdf(ray_in, hit_data, direction),
+            Self::Dielectric(_) | Self::DiffuseLight(_) => 0.,
+        }
+    }
+
     pub fn emit(&self, point: Vec3, u: f32, v: f32) -> Vec3 {
         match self {
             Self::Lambertian(lamb) => lamb.emit(point, u, v),
@@ -73,18 +113,27 @@ impl Lambertian {
         hit_data: &mut HitData,
         attenuation: &mut Vec3,
         scattered: &mut Ray,
+        sample: (f32, f32),
     ) -> bool {
-        let mut scatter_direction = hit_data.normal + Vec3::random_unit_vector();
-
-        // Catch degenerate scatter directions near zero from surface
-        if scatter_direction.near_zero() {
-            scatter_direction = hit_data.normal;
-        };
+        let scatter_direction = Onb::new(hit_data.normal).to_world(Vec3::cosine_hemisphere(sample));
         *scattered = Ray::new(hit_data.point, scatter_direction, ray_in.time);
         *attenuation = self.texture.value(hit_data.u, hit_data.v, hit_data.point);
         true
     }
 
+    pub fn eval(&self, _ray_in: Ray, hit_data: &HitData, direction: Vec3) -> Vec3 {
+        let cosine = Vec3::dot(hit_data.normal, direction.unit());
+        if cosine <= 0. {
+            return Vec3::ZERO;
+        }
+        (cosine / PI) * self.texture.value(hit_data.u, hit_data.v, hit_data.point)
+    }
+
+    // Directions are drawn with a density following the cosine to the normal
+    pub fn pdf(&self, _ray_in: Ray, hit_data: &HitData, direction: Vec3) -> f32 {
+        Vec3::dot(hit_data.normal, direction.unit()).max(0.) / PI
+    }
+
     pub fn emit(&self, _point: Vec3, _u: f32, _v: f32) -> Vec3 {
         Vec3::ZERO
     }
@@ -107,14 +156,50 @@ impl Metal {
         hit_data: &mut HitData,
         attenuation: &mut Vec3,
         scattered: &mut Ray,
+        sample: (f32, f32),
     ) -> bool {
         let reflected = Vec3::reflect(ray_in.direction, hit_data.normal).unit()
-            + self.fuzz * Vec3::random_unit_vector();
+            + self.fuzz * Vec3::uniform_sphere(sample);
         *scattered = Ray::new(hit_data.point, reflected, ray_in.time);
         *attenuation = self.albedo;
         Vec3::dot(scattered.direction, hit_data.normal) > 0.
     }
 
+    // Every direction above the surface is scattered with the albedo as attenuation,
+    // so the reflected fraction is the albedo weighted by the sampling density
+    pub fn eval(&self, ray_in: Ray, hit_data: &HitData, direction: Vec3) -> Vec3 {
+        self.pdf(ray_in, hit_data, direction) * self.albedo
+    }
+
+    // Scattered directions point through a uniform point on the sphere of radius fuzz
+    // around the tip of the unit mirror reflection. The density over the sphere,
+    // 1/(4π fuzz²), is converted to solid angle at every place the direction crosses
+    // the sphere at distance t, where it grows with t² over the cosine of the crossing
+    pub fn pdf(&self, ray_in: Ray, hit_data: &HitData, direction: Vec3) -> f32 {
+        let direction = direction.unit();
+        if self.fuzz <= 0. || Vec3::dot(direction, hit_data.normal) <= 0. {
+            return 0.;
+        }
+
+        let reflected = Vec3::reflect(ray_in.direction, hit_data.normal).unit();
+        let cosine = Vec3::dot(direction, reflected);
+        let discriminant = cosine * cosine - 1. + self.fuzz * self.fuzz;
+        if discriminant <= -1e-5 {
+            return 0.;
+        }
+
+        // The cosine at both crossings is sqrt(discriminant) / fuzz. Directions
+        // grazing the sphere may land a rounding error outside it, and are taken to
+        // graze it at a tiny angle instead of being given no density
+        let root = discriminant.max(1e-5).sqrt();
+        let distances_squared: f32 = [cosine - root, cosine + root]
+            .into_iter()
+            .filter(|&t| t > 0.)
+            .map(|t| t * t)
+            .sum();
+        distances_squared / (4. * PI * self.fuzz * root)
+    }
+
     pub fn emit(&self, _point: Vec3, _u: f32, _v: f32) -> Vec3 {
         Vec3::ZERO
     }
@@ -138,6 +223,7 @@ impl Dielectric {
         hit_data: &mut HitData,
         attenuation: &mut Vec3,
         scattered: &mut Ray,
+        sample: (f32, f32),
     ) -> bool {
         let adjusted_ref_ratio = if hit_data.front_face {
             1. / self.refractive_index
@@ -150,7 +236,7 @@ impl Dielectric {
         let sin_theta = (1. - cos_theta * cos_theta).sqrt();
 
         let direction = if adjusted_ref_ratio * sin_theta > 1.
-            || Dielectric::reflectance(cos_theta, adjusted_ref_ratio) > random_num()
+            || Dielectric::reflectance(cos_theta, adjusted_ref_ratio) > sample.0
         {
             Vec3::reflect(norm_incoming_vec, hit_data.normal)
         } else {
@@ -203,13 +289,177 @@ impl Isotropic {
         hit_data: &mut HitData,
         attenuation: &mut Vec3,
         scattered: &mut Ray,
+        sample: (f32, f32),
     ) -> bool {
-        *scattered = Ray::new(hit_data.point, Vec3::random_unit_vector(), ray_in.time);
+        *scattered = Ray::new(hit_data.point, Vec3::uniform_sphere(sample), ray_in.time);
         *attenuation = self.texture.value(hit_data.u, hit_data.v, hit_data.point);
         true
     }
 
+    pub fn eval(&self, ray_in: Ray, hit_data: &HitData, direction: Vec3) -> Vec3 {
+        self.pdf(ray_in, hit_data, direction)
+            * self.texture.value(hit_data.u, hit_data.v, hit_data.point)
+    }
+
+    // Scatters uniformly over the whole sphere of directions
+    pub fn pdf(&self, _ray_in: Ray, _hit_data: &HitData, _direction: Vec3) -> f32 {
+        1. / (4. * PI)
+    }
+
     pub fn emit(&self, _point: Vec3, _u: f32, _v: f32) -> Vec3 {
         Vec3::ZERO
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::{random_num, seeded_rng};
+
+    fn hit_facing_up() -> HitData {
+        HitData {
+            normal: Vec3::new(0., 1., 0.),
+            front_face: true,
+            ..HitData::default()
+        }
+    }
+
+    #[test]
+    fn diffuse_reflection_integrates_to_the_albedo() {
+        let albedo = Vec3::new(0.2, 0.5, 0.8);
+        let material = Material::Lambertian(Lambertian::new_from_color(albedo));
+        let hit_data = hit_facing_up();
+        let ray_in = Ray::new(Vec3::new(0., 1., 0.), Vec3::new(0., -1., 0.), 0.);
+        let mut rng = seeded_rng(9);
+
+        // Uniform directions over the sphere, of density 1 / 4pi
+        let count = 100_000;
+        let mut integral = Vec3::ZERO;
+        for _ in 0..count {
+            let direction = Vec3::random_unit_vector(&mut rng);
+            integral += material.eval(ray_in, &hit_data, direction);
+        }
+        let integral = (4. * PI / count as f32) * integral;
+        assert!((integral - albedo).length() < 0.01, "{integral:?}");
+    }
+
+    #[test]
+    fn diffuse_surfaces_reflect_nothing_from_below() {
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::splat(0.5)));
+        let ray_in = Ray::new(Vec3::new(0., 1., 0.), Vec3::new(0., -1., 0.), 0.);
+        let below = Vec3::new(0.3, -1., 0.);
+
+        assert!(!material.is_specular());
+        assert_eq!(material.eval(ray_in, &hit_facing_up(), below).x, 0.);
+        assert_eq!(material.pdf(ray_in, &hit_facing_up(), below), 0.);
+    }
+
+    #[test]
+    fn specular_materials_are_not_sampled_directly() {
+        let mirror = Material::Metal(Metal::new(Vec3::splat(0.9), 0.));
+        let glass = Material::Dielectric(Dielectric {
+            refractive_index: 1.5,
+        });
+        let ray_in = Ray::new(Vec3::new(0., 1., 0.), Vec3::new(0., -1., 0.), 0.);
+        let up = Vec3::new(0., 1., 0.);
+
+        for material in [mirror, glass] {
+            assert!(material.is_specular());
+            assert_eq!(material.eval(ray_in, &hit_facing_up(), up).length(), 0.);
+            assert_eq!(material.pdf(ray_in, &hit_facing_up(), up), 0.);
+        }
+    }
+
+    // Checks `pdf` against the directions `scatter` actually picks: the mean
+    // reciprocal density of scattered directions gives the solid angle they cover, and
+    // every scattered ray is attenuated by the ratio of `eval` to `pdf`. Returns the
+    // fraction of rays that scatter at all
+    fn assert_scattering_matches_pdf(material: &Material, ray_in: Ray, solid_angle: f32) -> f32 {
+        let hit_data = hit_facing_up();
+        let mut rng = seeded_rng(4);
+        let count = 100_000;
+
+        let mut scattered_count = 0;
+        let mut covered = 0.;
+        for _ in 0..count {
+            let (mut attenuation, mut scattered) = (Vec3::ZERO, Ray::default());
+            let mut hit = hit_data.clone();
+            let sample = (random_num(&mut rng), random_num(&mut rng));
+            if !material.scatter(ray_in, &mut hit, &mut attenuation, &mut scattered, sample) {
+                continue;
+            }
+            let pdf = material.pdf(ray_in, &hit_data, scattered.direction);
+            assert!(
+                pdf > 0.,
+                "scattered along {:?} with no density",
+                scattered.direction
+            );
+            let ratio = (1. / pdf) * material.eval(ray_in, &hit_data, scattered.direction);
+            assert!((ratio - attenuation).length() < 1e-3, "{ratio:?}");
+            scattered_count += 1;
+            covered += 1. / pdf;
+        }
+        let covered = covered / count as f32;
+        assert!(
+            (covered - solid_angle).abs() < 0.02 * solid_angle,
+            "scattered directions cover {covered} instead of {solid_angle}"
+        );
+
+        scattered_count as f32 / count as f32
+    }
+
+    // Integrates the density over the sphere of directions, which has to give the
+    // fraction of rays that scatter
+    fn assert_pdf_integrates_to(material: &Material, ray_in: Ray, fraction: f32) {
+        let mut rng = seeded_rng(5);
+        let count = 100_000;
+        let mut integral = 0.;
+        for _ in 0..count {
+            let direction = Vec3::random_unit_vector(&mut rng);
+            integral += material.pdf(ray_in, &hit_facing_up(), direction);
+        }
+        let integral = 4. * PI * integral / count as f32;
+        assert!(
+            (integral - fraction).abs() < 0.02,
+            "density integrates to {integral}, but {fraction} of the rays scatter"
+        );
+    }
+
+    #[test]
+    fn diffuse_density_matches_scattering() {
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::splat(0.5)));
+        let ray_in = Ray::new(Vec3::new(-1., 1., 0.), Vec3::new(1., -1., 0.), 0.);
+        let fraction = assert_scattering_matches_pdf(&material, ray_in, 2. * PI);
+        assert_eq!(fraction, 1.);
+        assert_pdf_integrates_to(&material, ray_in, 1.);
+    }
+
+    #[test]
+    fn fuzzy_metal_density_matches_scattering() {
+        // Reflected 30 degrees from the normal
+        let ray_in = Ray::new(Vec3::ZERO, Vec3::new(0.5, -(0.75f32).sqrt(), 0.), 0.);
+
+        // The cone of half angle asin(0.3) around the reflection stays above the
+        // surface, and every ray scatters
+        let metal = Material::Metal(Metal::new(Vec3::new(0.9, 0.8, 0.7), 0.3));
+        let cone = 2. * PI * (1. - (1f32 - 0.09).sqrt());
+        assert_eq!(assert_scattering_matches_pdf(&metal, ray_in, cone), 1.);
+
+        // With a fuzz of one, directions spread over the hemisphere around the
+        // reflection, which is tilted partly below the surface. The rays scattered
+        // into the surface are lost, leaving a lune of 2pi less twice the tilt
+        let metal = Material::Metal(Metal::new(Vec3::new(0.9, 0.8, 0.7), 1.));
+        let fraction = assert_scattering_matches_pdf(&metal, ray_in, 5. * PI / 3.);
+        assert!(fraction < 0.95, "{fraction}");
+        assert_pdf_integrates_to(&metal, ray_in, fraction);
+    }
+
+    #[test]
+    fn isotropic_density_matches_scattering() {
+        let material = Material::Isotropic(Isotropic::new_from_color(Vec3::splat(0.5)));
+        let ray_in = Ray::new(Vec3::ZERO, Vec3::new(0., -1., 0.), 0.);
+        let fraction = assert_scattering_matches_pdf(&material, ray_in, 4. * PI);
+        assert_eq!(fraction, 1.);
+        assert_pdf_integrates_to(&material, ray_in, 1.);
+    }
+}
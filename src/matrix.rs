@@ -0,0 +1,831 @@
+use std::ops::Mul;
+
+use crate::{
+    aabb::Aabb,
+    interval::Interval,
+    ray::Ray,
+    utilities::degrees_to_radians,
+    vec3::{Dim, Vec3},
+};
+
+// Row-major 4x4 matrix acting on column vectors, with points taking a fourth
+// coordinate of one and directions a fourth coordinate of zero
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub rows: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self {
+        rows: [
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ],
+    };
+
+    pub fn new(rows: [[f32; 4]; 4]) -> Self {
+        Self { rows }
+    }
+
+    // Affine matrix from the 3x3 linear part and a translation
+    pub fn new_from_linear(linear: [[f32; 3]; 3], offset: Vec3) -> Self {
+        let [x, y, z] = linear;
+        Self::new([
+            [x[0], x[1], x[2], offset.x],
+            [y[0], y[1], y[2], offset.y],
+            [z[0], z[1], z[2], offset.z],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new_from_linear([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]], offset)
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::new_from_linear(
+            [
+                [factors.x, 0., 0.],
+                [0., factors.y, 0.],
+                [0., 0., factors.z],
+            ],
+            Vec3::ZERO,
+        )
+    }
+
+    // Counter-clockwise rotation in degrees about the given axis when looking down
+    // it towards the origin
+    pub fn rotation(axis: Vec3, theta: f32) -> Self {
+        let Vec3 { x, y, z } = axis.unit();
+        let radians = degrees_to_radians(theta);
+        let (sin, cos) = radians.sin_cos();
+        let t = 1. - cos;
+
+        Self::new_from_linear(
+            [
+                [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y],
+                [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x],
+                [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos],
+            ],
+            Vec3::ZERO,
+        )
+    }
+
+    // Shear moving each coordinate in proportion to the other two, so that for
+    // instance `xy` is how far x moves per unit of y
+    pub fn shearing(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+        Self::new_from_linear([[1., xy, xz], [yx, 1., yz], [zx, zy, 1.]], Vec3::ZERO)
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry = self.rows[j][i];
+            }
+        }
+        Self::new(rows)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting. Returns None for singular
+    // matrices, such as scalings by zero
+    pub fn inverse(&self) -> Option<Self> {
+        let mut matrix = self.rows;
+        let mut inverse = Self::IDENTITY.rows;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))?;
+            if matrix[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            matrix.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1. / matrix[column][column];
+            for j in 0..4 {
+                matrix[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+
+            for row in 0..4 {
+                let factor = matrix[row][column];
+                if row == column || factor == 0. {
+                    continue;
+                }
+                for j in 0..4 {
+                    matrix[row][j] -= factor * matrix[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+
+        Some(Self::new(inverse))
+    }
+
+    // Determinant of the 3x3 linear part, the factor by which volumes are scaled
+    pub fn linear_determinant(&self) -> f32 {
+        let [x, y, z, _] = self.rows;
+        x[0] * (y[1] * z[2] - y[2] * z[1]) - x[1] * (y[0] * z[2] - y[2] * z[0])
+            + x[2] * (y[0] * z[1] - y[1] * z[0])
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.transform_vector(point) + Vec3::new(self.rows[0][3], self.rows[1][3], self.rows[2][3])
+    }
+
+    pub fn transform_vector(&self, vec: Vec3) -> Vec3 {
+        let [x, y, z, _] = self.rows;
+        Vec3::new(
+            x[0] * vec.x + x[1] * vec.y + x[2] * vec.z,
+            y[0] * vec.x + y[1] * vec.y + y[2] * vec.z,
+            z[0] * vec.x + z[1] * vec.y + z[2] * vec.z,
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry = (0..4).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Self::new(rows)
+    }
+}
+
+// Affine transformation kept together with its inverse. Transformations are built
+// by chaining, with every step applied after the ones before it, so
+// `Transform::identity().scale(..).rotate_y(..).translate(..)` scales first and
+// translates last
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            matrix: Mat4::IDENTITY,
+            inverse: Mat4::IDENTITY,
+        }
+    }
+
+    // Returns None when the matrix cannot be inverted
+    pub fn new(matrix: Mat4) -> Option<Self> {
+        Some(Self {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        self.matrix
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    // Applies `other` after this transformation
+    pub fn then(self, other: Self) -> Self {
+        Self {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    pub fn translate(self, offset: Vec3) -> Self {
+        self.then(Self {
+            matrix: Mat4::translation(offset),
+            inverse: Mat4::translation(-offset),
+        })
+    }
+
+    // Panics if any factor is zero, which would flatten objects out of existence
+    pub fn scale(self, factors: Vec3) -> Self {
+        self.try_scale(factors)
+            .expect("scale factors must be non-zero")
+    }
+
+    // Like scale, but returns None for a zero factor instead of panicking
+    pub(crate) fn try_scale(self, factors: Vec3) -> Option<Self> {
+        if factors.x == 0. || factors.y == 0. || factors.z == 0. {
+            return None;
+        }
+        Some(self.then(Self {
+            matrix: Mat4::scaling(factors),
+            inverse: Mat4::scaling(Vec3::new(1. / factors.x, 1. / factors.y, 1. / factors.z)),
+        }))
+    }
+
+    // Rotation in degrees about an axis through the origin
+    pub fn rotate(self, axis: Vec3, theta: f32) -> Self {
+        let matrix = Mat4::rotation(axis, theta);
+        self.then(Self {
+            matrix,
+            inverse: matrix.transpose(),
+        })
+    }
+
+    pub fn rotate_x(self, theta: f32) -> Self {
+        self.rotate(Vec3::new(1., 0., 0.), theta)
+    }
+
+    pub fn rotate_y(self, theta: f32) -> Self {
+        self.rotate(Vec3::new(0., 1., 0.), theta)
+    }
+
+    pub fn rotate_z(self, theta: f32) -> Self {
+        self.rotate(Vec3::new(0., 0., 1.), theta)
+    }
+
+    // Panics if the shear collapses space onto a plane, see Mat4::shearing
+    pub fn shear(self, xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+        let shear = Self::new(Mat4::shearing(xy, xz, yx, yz, zx, zy))
+            .expect("shear must not collapse space onto a plane");
+        self.then(shear)
+    }
+
+    pub fn point(&self, point: Vec3) -> Vec3 {
+        self.matrix.transform_point(point)
+    }
+
+    pub fn vector(&self, vec: Vec3) -> Vec3 {
+        self.matrix.transform_vector(vec)
+    }
+
+    // Normals are carried by the inverse transpose so that they stay perpendicular to
+    // surfaces under non-uniform scaling and shearing. The result is not normalized
+    pub fn normal(&self, normal: Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(normal)
+    }
+
+    // Factor converting a density over directions in the space this transformation
+    // maps from into one over the transformed directions, at the given transformed
+    // direction. Solid angles around a direction d are scaled by |det| / |M d|³
+    pub fn direction_density(&self, direction: Vec3) -> f32 {
+        let local = self.inverse.transform_vector(direction.unit());
+        1. / (self.matrix.linear_determinant().abs() * local.length().powi(3))
+    }
+
+    // Ray in the space this transformation maps from. The direction is not
+    // normalized, so distances along both rays match
+    pub fn ray_to_local(&self, ray: Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point(ray.origin),
+            self.inverse.transform_vector(ray.direction),
+            ray.time,
+        )
+    }
+
+    // Smallest box containing the transformed box. Every output coordinate is a sum of
+    // terms each depending on a single input coordinate, so picking the smaller and
+    // larger end of every term gives the exact bounds of the eight transformed corners
+    pub fn bounding_box(&self, bbox: Aabb) -> Aabb {
+        let input = [bbox.x, bbox.y, bbox.z];
+        let [x, y, z] = [0, 1, 2].map(|i| {
+            let row = self.matrix.rows[i];
+            let (mut min, mut max) = (row[3], row[3]);
+            for (j, interval) in input.iter().enumerate() {
+                let a = row[j] * interval.min;
+                let b = row[j] * interval.max;
+                min += a.min(b);
+                max += a.max(b);
+            }
+            Interval::new(min, max)
+        });
+
+        Aabb::new_from_intervals(x, y, z)
+    }
+}
+
+// Unit quaternion describing a rotation, used to interpolate between orientations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Self = Self {
+        w: 1.,
+        x: 0.,
+        y: 0.,
+        z: 0.,
+    };
+
+    // Rotation in degrees about the given axis, matching Mat4::rotation
+    pub fn new_from_axis_angle(axis: Vec3, theta: f32) -> Self {
+        let axis = axis.unit();
+        let (sin, cos) = (0.5 * degrees_to_radians(theta)).sin_cos();
+        Self {
+            w: cos,
+            x: sin * axis.x,
+            y: sin * axis.y,
+            z: sin * axis.z,
+        }
+    }
+
+    fn dot(self, other: Self) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn normalized(self) -> Self {
+        let scale = 1. / self.dot(self).sqrt();
+        Self {
+            w: scale * self.w,
+            x: scale * self.x,
+            y: scale * self.y,
+            z: scale * self.z,
+        }
+    }
+
+    // Spherical linear interpolation along the shorter arc between the rotations
+    pub fn slerp(self, other: Self, t: f32) -> Self {
+        let mut cos = self.dot(other);
+        let mut other = other;
+        if cos < 0. {
+            cos = -cos;
+            other = Self {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            };
+        }
+
+        // Nearly identical rotations make the arc numerically unstable, where a plain
+        // interpolation is just as accurate
+        let (a, b) = if cos > 0.9995 {
+            (1. - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1. - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+
+        Self {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        }
+        .normalized()
+    }
+
+    pub fn to_matrix(self) -> Mat4 {
+        let Self { w, x, y, z } = self.normalized();
+        Mat4::new_from_linear(
+            [
+                [
+                    1. - 2. * (y * y + z * z),
+                    2. * (x * y - w * z),
+                    2. * (x * z + w * y),
+                ],
+                [
+                    2. * (x * y + w * z),
+                    1. - 2. * (x * x + z * z),
+                    2. * (y * z - w * x),
+                ],
+                [
+                    2. * (x * z - w * y),
+                    2. * (y * z + w * x),
+                    1. - 2. * (x * x + y * y),
+                ],
+            ],
+            Vec3::ZERO,
+        )
+    }
+}
+
+// Pose of an animated object at one moment, applied as scale, then rotation, then
+// translation
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(translation: Vec3) -> Self {
+        Self {
+            translation,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::splat(1.),
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    // Panics if any factor is zero, like Transform::scale
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        assert!(
+            self.scale_is_non_zero(),
+            "keyframe scale factors must be non-zero"
+        );
+        self
+    }
+
+    fn scale_is_non_zero(&self) -> bool {
+        Dim::ALL.into_iter().all(|dim| self.scale.get(dim) != 0.)
+    }
+
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        Self {
+            translation: (1. - t) * self.translation + t * other.translation,
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: (1. - t) * self.scale + t * other.scale,
+        }
+    }
+
+    // None when a scale factor is zero, since nothing can undo the flattening
+    fn transform(self) -> Option<Transform> {
+        let rotation = self.rotation.to_matrix();
+        let transform = Transform::identity()
+            .try_scale(self.scale)?
+            .then(Transform {
+                matrix: rotation,
+                inverse: rotation.transpose(),
+            })
+            .translate(self.translation);
+        Some(transform)
+    }
+}
+
+// Transformation changing over time through keyframes at given ray times. Poses are
+// interpolated between the surrounding keyframes and held before the first and
+// after the last
+#[derive(Clone, Debug, Default)]
+pub struct AnimatedTransform {
+    keyframes: Vec<(f32, Keyframe)>,
+}
+
+// Poses sampled between two keyframes when bounding the motion between them
+const MOTION_BOUND_STEPS: usize = 32;
+
+impl AnimatedTransform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keyframes may be added in any order. Panics if a scale factor of the keyframe
+    // is zero or has the opposite sign of the same factor in a neighbouring keyframe,
+    // as the scale interpolated between them would then pass through zero
+    pub fn keyframe(mut self, time: f32, keyframe: Keyframe) -> Self {
+        let index = self.keyframes.partition_point(|&(t, _)| t <= time);
+        assert!(
+            keyframe.scale_is_non_zero(),
+            "keyframe scale factors must be non-zero"
+        );
+        let neighbours = [index.checked_sub(1), Some(index)]
+            .into_iter()
+            .flatten()
+            .filter_map(|i| self.keyframes.get(i));
+        for (_, neighbour) in neighbours {
+            for dim in Dim::ALL {
+                assert!(
+                    keyframe.scale.get(dim) * neighbour.scale.get(dim) > 0.,
+                    "keyframe scale factors must keep their sign between keyframes"
+                );
+            }
+        }
+
+        self.keyframes.insert(index, (time, keyframe));
+        self
+    }
+
+    pub fn pose_at(&self, time: f32) -> Keyframe {
+        let index = self.keyframes.partition_point(|&(t, _)| t <= time);
+        match (index, self.keyframes.len()) {
+            (_, 0) => Keyframe::new(Vec3::ZERO),
+            (0, _) => self.keyframes[0].1,
+            (index, len) if index == len => self.keyframes[len - 1].1,
+            (index, _) => {
+                let (start_time, start) = self.keyframes[index - 1];
+                let (end_time, end) = self.keyframes[index];
+                start.interpolate(end, (time - start_time) / (end_time - start_time))
+            }
+        }
+    }
+
+    // None when the pose at that time flattens the object, which validated
+    // keyframes never interpolate to
+    pub fn at(&self, time: f32) -> Option<Transform> {
+        self.pose_at(time).transform()
+    }
+
+    // Box containing the given box for the whole animation. Poses are sampled between
+    // keyframes, and each sampled box is padded by the distance the box corners travel
+    // to the next sample, which covers any motion in between
+    pub fn bounding_box(&self, bbox: Aabb) -> Aabb {
+        let Some(&(first_time, _)) = self.keyframes.first() else {
+            return bbox;
+        };
+
+        let mut times = vec![first_time];
+        for pair in self.keyframes.windows(2) {
+            let (start, end) = (pair[0].0, pair[1].0);
+            times.extend(
+                (1..=MOTION_BOUND_STEPS)
+                    .map(|step| start + (end - start) * step as f32 / MOTION_BOUND_STEPS as f32),
+            );
+        }
+
+        let corners: Vec<Vec3> = [bbox.x.min, bbox.x.max]
+            .into_iter()
+            .flat_map(|x| [bbox.y.min, bbox.y.max].map(|y| (x, y)))
+            .flat_map(|(x, y)| [bbox.z.min, bbox.z.max].map(|z| Vec3::new(x, y, z)))
+            .collect();
+
+        let transforms: Vec<Transform> = times.iter().filter_map(|&time| self.at(time)).collect();
+        if transforms.is_empty() {
+            return bbox;
+        }
+        let mut motion_box = transforms[0].bounding_box(bbox);
+        for pair in transforms.windows(2) {
+            let travel = corners
+                .iter()
+                .map(|&corner| (pair[1].point(corner) - pair[0].point(corner)).length())
+                .fold(0., f32::max);
+            let padded = pair[0].bounding_box(bbox);
+            let padded = Aabb::new_from_intervals(
+                padded.x.expands(2. * travel),
+                padded.y.expands(2. * travel),
+                padded.z.expands(2. * travel),
+            );
+            motion_box = Aabb::new_from_boxes(motion_box, padded);
+        }
+
+        Aabb::new_from_boxes(
+            motion_box,
+            transforms[transforms.len() - 1].bounding_box(bbox),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{a:?} is not {b:?}");
+    }
+
+    #[test]
+    fn steps_apply_in_chaining_order() {
+        let transform = Transform::identity()
+            .scale(Vec3::new(2., 2., 2.))
+            .rotate_z(90.)
+            .translate(Vec3::new(1., 0., 0.));
+
+        // (1, 0, 0) is scaled to (2, 0, 0), turned onto (0, 2, 0) and then moved
+        assert_close(
+            transform.point(Vec3::new(1., 0., 0.)),
+            Vec3::new(1., 2., 0.),
+        );
+        assert_close(
+            transform.vector(Vec3::new(1., 0., 0.)),
+            Vec3::new(0., 2., 0.),
+        );
+    }
+
+    #[test]
+    fn inverse_undoes_the_transformation() {
+        let transform = Transform::identity()
+            .shear(0.5, 0., 0., 0.25, 0., 0.)
+            .scale(Vec3::new(1., -3., 0.5))
+            .rotate(Vec3::new(1., 1., 0.), 30.)
+            .translate(Vec3::new(4., 5., 6.));
+        let point = Vec3::new(0.3, -1.2, 2.5);
+
+        assert_close(transform.inverse().point(transform.point(point)), point);
+        let product = transform.matrix() * transform.inverse().matrix();
+        for (i, row) in product.rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let expected = if i == j { 1. } else { 0. };
+                assert!((value - expected).abs() < 1e-5, "{product:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert!(Mat4::scaling(Vec3::new(1., 0., 1.)).inverse().is_none());
+        assert!(Transform::new(Mat4::scaling(Vec3::new(1., 0., 1.))).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero")]
+    fn zero_scale_is_rejected() {
+        Transform::identity().scale(Vec3::new(1., 0., 1.));
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scaling() {
+        let transform = Transform::identity().scale(Vec3::new(4., 1., 1.));
+        // Surface of the plane x + y = 0, with its normal along (1, 1, 0)
+        let tangent = transform.vector(Vec3::new(1., -1., 0.));
+        let normal = transform.normal(Vec3::new(1., 1., 0.));
+
+        assert!(Vec3::dot(tangent, normal).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bounding_box_matches_the_transformed_corners() {
+        let transform = Transform::identity()
+            .rotate(Vec3::new(1., 2., 3.), 40.)
+            .scale(Vec3::new(1., 2., 0.5))
+            .translate(Vec3::new(-1., 0., 3.));
+        let bbox = Aabb::new_from_points(Vec3::new(-1., 0., 2.), Vec3::new(3., 1., 5.));
+        let transformed = transform.bounding_box(bbox);
+
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        for x in [bbox.x.min, bbox.x.max] {
+            for y in [bbox.y.min, bbox.y.max] {
+                for z in [bbox.z.min, bbox.z.max] {
+                    let corner = transform.point(Vec3::new(x, y, z));
+                    min = Vec3::new(
+                        min.x.min(corner.x),
+                        min.y.min(corner.y),
+                        min.z.min(corner.z),
+                    );
+                    max = Vec3::new(
+                        max.x.max(corner.x),
+                        max.y.max(corner.y),
+                        max.z.max(corner.z),
+                    );
+                }
+            }
+        }
+        assert_close(
+            Vec3::new(transformed.x.min, transformed.y.min, transformed.z.min),
+            min,
+        );
+        assert_close(
+            Vec3::new(transformed.x.max, transformed.y.max, transformed.z.max),
+            max,
+        );
+    }
+
+    #[test]
+    fn local_rays_keep_distances() {
+        let transform = Transform::identity()
+            .scale(Vec3::new(2., 3., 4.))
+            .translate(Vec3::new(1., 1., 1.));
+        let ray = Ray::new(Vec3::new(0., 0., 0.), Vec3::new(1., 2., 3.), 0.5);
+        let local = transform.ray_to_local(ray);
+
+        assert_eq!(local.time, 0.5);
+        assert_close(transform.point(local.at(2.5)), ray.at(2.5));
+    }
+    fn quat_close(a: Quat, b: Quat) -> bool {
+        // q and -q describe the same rotation
+        let dot = a.w * b.w + a.x * b.x + a.y * b.y + a.z * b.z;
+        (dot.abs() - 1.).abs() < 1e-5
+    }
+
+    #[test]
+    fn slerp_follows_the_shorter_arc() {
+        let up = Vec3::new(0., 1., 0.);
+        let start = Quat::new_from_axis_angle(up, 0.);
+        let end = Quat::new_from_axis_angle(up, 90.);
+
+        assert!(quat_close(start.slerp(end, 0.), start));
+        assert!(quat_close(start.slerp(end, 1.), end));
+        assert!(quat_close(
+            start.slerp(end, 0.5),
+            Quat::new_from_axis_angle(up, 45.)
+        ));
+        // 270 degrees one way is 90 degrees the other, so halfway is -45 degrees
+        assert!(quat_close(
+            start.slerp(Quat::new_from_axis_angle(up, 270.), 0.5),
+            Quat::new_from_axis_angle(up, -45.)
+        ));
+    }
+
+    #[test]
+    fn quaternion_matrix_matches_the_rotation_matrix() {
+        let axis = Vec3::new(1., -2., 0.5);
+        let from_quat = Quat::new_from_axis_angle(axis, 70.).to_matrix();
+        let direct = Mat4::rotation(axis, 70.);
+        let point = Vec3::new(0.3, 1., -2.);
+
+        assert_close(
+            from_quat.transform_point(point),
+            direct.transform_point(point),
+        );
+    }
+
+    fn animation() -> AnimatedTransform {
+        let up = Vec3::new(0., 1., 0.);
+        AnimatedTransform::new()
+            .keyframe(
+                1.,
+                Keyframe::new(Vec3::new(4., 0., 0.))
+                    .with_rotation(Quat::new_from_axis_angle(up, 90.))
+                    .with_scale(Vec3::new(3., 1., 1.)),
+            )
+            .keyframe(0., Keyframe::new(Vec3::ZERO))
+    }
+
+    #[test]
+    fn poses_are_interpolated_between_keyframes() {
+        let animation = animation();
+        let point = Vec3::new(1., 0., 0.);
+
+        assert_close(animation.at(0.).unwrap().point(point), point);
+        // Scaled to (3, 0, 0), turned onto (0, 0, -3) and moved to (4, 0, -3)
+        assert_close(
+            animation.at(1.).unwrap().point(point),
+            Vec3::new(4., 0., -3.),
+        );
+        // Halfway: scaled by 2, turned by 45 degrees and moved by 2
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(
+            animation.at(0.5).unwrap().point(point),
+            Vec3::new(2. + 2. * half, 0., -2. * half),
+        );
+        // Poses are held outside the keyframes
+        assert_close(
+            animation.at(-1.).unwrap().point(point),
+            animation.at(0.).unwrap().point(point),
+        );
+        assert_close(
+            animation.at(2.).unwrap().point(point),
+            animation.at(1.).unwrap().point(point),
+        );
+    }
+
+    #[test]
+    fn swept_bounding_box_covers_every_pose() {
+        let animation = animation();
+        let bbox = Aabb::new_from_points(Vec3::new(-1., -1., -1.), Vec3::new(1., 1., 1.));
+        let swept = animation.bounding_box(bbox);
+
+        for step in 0..=200 {
+            let pose = animation.at(step as f32 / 200.).unwrap().bounding_box(bbox);
+            for dim in Dim::ALL {
+                assert!(swept.get(dim).min <= pose.get(dim).min + 1e-5);
+                assert!(swept.get(dim).max >= pose.get(dim).max - 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero")]
+    fn zero_keyframe_scale_is_rejected() {
+        Keyframe::new(Vec3::ZERO).with_scale(Vec3::new(1., 0., 1.));
+    }
+
+    #[test]
+    #[should_panic(expected = "keep their sign")]
+    fn scale_passing_through_zero_is_rejected() {
+        AnimatedTransform::new()
+            .keyframe(0., Keyframe::new(Vec3::ZERO))
+            .keyframe(2., Keyframe::new(Vec3::ZERO))
+            .keyframe(
+                1.,
+                Keyframe::new(Vec3::ZERO).with_scale(Vec3::new(1., -1., 1.)),
+            );
+    }
+
+    #[test]
+    fn flattened_pose_has_no_transform() {
+        let keyframe = Keyframe {
+            scale: Vec3::new(1., 0., 1.),
+            ..Keyframe::new(Vec3::ZERO)
+        };
+        assert!(keyframe.transform().is_none());
+    }
+}
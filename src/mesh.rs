@@ -0,0 +1,339 @@
+use crate::{
+    aabb::Aabb,
+    bvh::{Bvh, LinearBvh, SplitMethod},
+    hittable::{HitData, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    triangle::{intersect_triangle, sample_triangle, triangle_pdf},
+    utilities::{pick_with_sample, RenderRng},
+    vec3::Vec3,
+};
+
+// Largest number of faces kept together in a leaf of the acceleration structure
+const MAX_FACES_PER_LEAF: usize = 4;
+
+// Centroid bins evaluated by the surface area heuristic when splitting faces
+const SPLIT_BINS: usize = 12;
+
+// One triangle of a mesh, given as indices into the vertex buffers shared by all faces
+#[derive(Clone, Copy, Debug)]
+pub struct MeshFace {
+    pub positions: [u32; 3],
+    pub normals: Option<[u32; 3]>,
+    pub uvs: Option<[u32; 3]>,
+    pub material: u32, // Index into the materials of the mesh
+}
+
+impl MeshFace {
+    pub fn new(positions: [u32; 3]) -> Self {
+        Self {
+            positions,
+            normals: None,
+            uvs: None,
+            material: 0,
+        }
+    }
+}
+
+// Indexed triangle mesh acting as a single hittable object. Vertex data is stored
+// once and shared between faces, and the faces are organized into an internal
+// bounding volume hierarchy so that large meshes stay cheap to intersect
+pub struct TriangleMesh {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f32, f32)>,
+    faces: Vec<MeshFace>,
+    materials: Vec<Material>,
+    tree: LinearBvh,
+}
+
+impl TriangleMesh {
+    // Mesh without normals or texture coordinates whose faces all share one material
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[u32; 3]>, material: Material) -> Self {
+        let faces = indices.into_iter().map(MeshFace::new).collect();
+        Self::new_from_faces(positions, Vec::new(), Vec::new(), faces, vec![material])
+    }
+
+    // Panics if a face references a vertex, normal, texture coordinate or material
+    // that is missing from the given buffers
+    pub fn new_from_faces(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f32, f32)>,
+        faces: Vec<MeshFace>,
+        materials: Vec<Material>,
+    ) -> Self {
+        let in_range = |indices: &[u32; 3], len: usize| indices.iter().all(|&i| (i as usize) < len);
+        for face in faces.iter() {
+            assert!(
+                in_range(&face.positions, positions.len()),
+                "mesh face references a missing vertex position"
+            );
+            assert!(
+                face.normals.map_or(true, |i| in_range(&i, normals.len())),
+                "mesh face references a missing vertex normal"
+            );
+            assert!(
+                face.uvs.map_or(true, |i| in_range(&i, uvs.len())),
+                "mesh face references a missing texture coordinate"
+            );
+            assert!(
+                (face.material as usize) < materials.len(),
+                "mesh face references a missing material"
+            );
+        }
+
+        let boxes: Vec<Aabb> = faces
+            .iter()
+            .map(|face| {
+                let [a, b, c] = Self::vertices(&positions, face);
+                Aabb::new_from_boxes(Aabb::new_from_points(a, b), Aabb::new_from_points(a, c))
+            })
+            .collect();
+        let (tree, order) = Bvh::init()
+            .split_method(SplitMethod::Sah { bins: SPLIT_BINS })
+            .max_leaf_size(MAX_FACES_PER_LEAF)
+            .build_linear(&boxes);
+        let faces = order.into_iter().map(|index| faces[index]).collect();
+
+        Self {
+            positions,
+            normals: normals.into_iter().map(|normal| normal.unit()).collect(),
+            uvs,
+            faces,
+            materials,
+            tree,
+        }
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    fn vertices(positions: &[Vec3], face: &MeshFace) -> [Vec3; 3] {
+        face.positions.map(|i| positions[i as usize])
+    }
+
+    fn fill_hit_data(
+        &self,
+        face: &MeshFace,
+        ray: Ray,
+        hit: (f32, f32, f32),
+        hit_data: &mut HitData,
+    ) {
+        let (t_intersection, beta, gamma) = hit;
+        let alpha = 1. - beta - gamma;
+        let [a, b, c] = Self::vertices(&self.positions, face);
+
+        hit_data.hit_along_ray = t_intersection;
+        hit_data.point = ray.at(t_intersection);
+        hit_data.material = Some(self.materials[face.material as usize].clone());
+        hit_data.set_face_normal(ray, Vec3::cross(b - a, c - a).unit());
+
+        if let Some(indices) = face.normals {
+            // Shading normal oriented to the same side of the surface as the ray
+            let [normal_a, normal_b, normal_c] = indices.map(|i| self.normals[i as usize]);
+            let shading_normal = (alpha * normal_a + beta * normal_b + gamma * normal_c).unit();
+            hit_data.normal = if hit_data.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+
+        // Without texture coordinates the vertices act as the corners (0,0), (1,0), (0,1)
+        (hit_data.u, hit_data.v) = match face.uvs {
+            Some(indices) => {
+                let [(u_a, v_a), (u_b, v_b), (u_c, v_c)] = indices.map(|i| self.uvs[i as usize]);
+                (
+                    alpha * u_a + beta * u_b + gamma * u_c,
+                    alpha * v_a + beta * v_b + gamma * v_c,
+                )
+            }
+            None => (beta, gamma),
+        };
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        _rng: &mut RenderRng,
+    ) -> bool {
+        let mut hit_face = None;
+        self.tree.traverse(ray, interval, |index, interval| {
+            let face = &self.faces[index];
+            let [a, b, c] = Self::vertices(&self.positions, face);
+            let hit = intersect_triangle(a, b - a, c - a, ray, interval)?;
+            hit_face = Some((face, hit));
+            Some(hit.0)
+        });
+
+        match hit_face {
+            Some((face, hit)) => {
+                self.fill_hit_data(face, ray, hit, hit_data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounding_box()
+    }
+
+    // Mixture picking every face with the same probability and a uniform point on it.
+    // The density sums over all faces the ray passes through
+    fn pdf_value(&self, ray: Ray, _rng: &mut RenderRng) -> f32 {
+        if self.faces.is_empty() {
+            return 0.;
+        }
+
+        let mut pdf = 0.;
+        let interval = Interval::new(0.001, f32::INFINITY);
+        self.tree.traverse(ray, interval, |index, interval| {
+            let [a, b, c] = Self::vertices(&self.positions, &self.faces[index]);
+            if let Some((t, _, _)) = intersect_triangle(a, b - a, c - a, ray, interval) {
+                pdf += triangle_pdf(b - a, c - a, ray, t);
+            }
+            // Not reporting the hit keeps the whole ray searched
+            None
+        });
+        pdf / self.faces.len() as f32
+    }
+
+    fn random(&self, origin: Vec3, _time: f32, sample: (f32, f32)) -> Vec3 {
+        if self.faces.is_empty() {
+            return Vec3::new(1., 0., 0.);
+        }
+        let (index, sample) = pick_with_sample(sample, self.faces.len());
+        let [a, b, c] = Self::vertices(&self.positions, &self.faces[index]);
+        sample_triangle(a, b - a, c - a, sample) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::assert_sampling_is_consistent;
+    use crate::material::Lambertian;
+    use crate::utilities::seeded_rng;
+    use std::f32::consts::PI;
+
+    fn material() -> Material {
+        Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.5, 0.5, 0.5)))
+    }
+
+    // Unit square in the z = -1 plane split into a grid of cells of two triangles,
+    // with texture coordinates matching the x and y position of each vertex
+    fn grid(cells: u32) -> TriangleMesh {
+        let step = 1. / cells as f32;
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        for y in 0..=cells {
+            for x in 0..=cells {
+                positions.push(Vec3::new(x as f32 * step, y as f32 * step, -1.));
+                uvs.push((x as f32 * step, y as f32 * step));
+            }
+        }
+
+        let vertex = |x: u32, y: u32| y * (cells + 1) + x;
+        let mut faces = Vec::new();
+        for y in 0..cells {
+            for x in 0..cells {
+                let corners = [vertex(x, y), vertex(x + 1, y), vertex(x + 1, y + 1)];
+                let other = [vertex(x, y), vertex(x + 1, y + 1), vertex(x, y + 1)];
+                for positions in [corners, other] {
+                    faces.push(MeshFace {
+                        uvs: Some(positions),
+                        ..MeshFace::new(positions)
+                    });
+                }
+            }
+        }
+        TriangleMesh::new_from_faces(positions, Vec::new(), uvs, faces, vec![material()])
+    }
+
+    fn hit(mesh: &TriangleMesh, ray: Ray) -> Option<HitData> {
+        let mut hit_data = HitData::default();
+        let interval = Interval::new(0.001, f32::INFINITY);
+        mesh.hit(ray, interval, &mut hit_data, &mut seeded_rng(0))
+            .then_some(hit_data)
+    }
+
+    #[test]
+    fn hit_interpolates_texture_coordinates() {
+        let mesh = grid(8);
+        assert_eq!(mesh.face_count(), 128);
+
+        for (x, y) in [(0.1, 0.2), (0.55, 0.45), (0.93, 0.71)] {
+            let ray = Ray::new(Vec3::new(x, y, 0.), Vec3::new(0., 0., -1.), 0.);
+            let hit_data = hit(&mesh, ray).expect("ray should hit the mesh");
+            assert!((hit_data.hit_along_ray - 1.).abs() < 1e-5);
+            assert!((hit_data.u - x).abs() < 1e-5 && (hit_data.v - y).abs() < 1e-5);
+            assert!(hit_data.front_face);
+        }
+    }
+
+    #[test]
+    fn rays_outside_the_mesh_miss() {
+        let mesh = grid(8);
+        let beside = Ray::new(Vec3::new(1.5, 0.5, 0.), Vec3::new(0., 0., -1.), 0.);
+        let away = Ray::new(Vec3::new(0.5, 0.5, 0.), Vec3::new(0., 0., 1.), 0.);
+
+        assert!(hit(&mesh, beside).is_none());
+        assert!(hit(&mesh, away).is_none());
+    }
+
+    #[test]
+    fn nearest_of_stacked_faces_is_hit() {
+        let positions = vec![
+            Vec3::new(0., 0., -3.),
+            Vec3::new(1., 0., -3.),
+            Vec3::new(0., 1., -3.),
+            Vec3::new(0., 0., -2.),
+            Vec3::new(1., 0., -2.),
+            Vec3::new(0., 1., -2.),
+        ];
+        let mesh = TriangleMesh::new(positions, vec![[0, 1, 2], [3, 4, 5]], material());
+        let ray = Ray::new(Vec3::new(0.25, 0.25, 0.), Vec3::new(0., 0., -1.), 0.);
+
+        let hit_data = hit(&mesh, ray).expect("ray should hit the mesh");
+        assert!((hit_data.hit_along_ray - 2.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bounding_box_spans_every_vertex() {
+        let bbox = grid(3).bounding_box();
+        assert!(bbox.x.min <= 0. && bbox.x.max >= 1.);
+        assert!(bbox.y.min <= 0. && bbox.y.max >= 1.);
+        assert!(bbox.z.contains(-1.));
+    }
+
+    #[test]
+    #[should_panic(expected = "missing texture coordinate")]
+    fn faces_must_reference_existing_buffers() {
+        let positions = vec![Vec3::new(0., 0., 0.); 3];
+        let face = MeshFace {
+            uvs: Some([0, 1, 2]),
+            ..MeshFace::new([0, 1, 2])
+        };
+        TriangleMesh::new_from_faces(
+            positions,
+            Vec::new(),
+            Vec::new(),
+            vec![face],
+            vec![material()],
+        );
+    }
+
+    #[test]
+    fn sampling_matches_the_density() {
+        // The unit square is a quarter of the face of the cube around the origin
+        assert_sampling_is_consistent(&grid(3), Vec3::ZERO, PI / 6.);
+    }
+}
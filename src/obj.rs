@@ -0,0 +1,639 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use image::ImageError;
+
+use crate::{
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    mesh::{MeshFace, TriangleMesh},
+    texture::{ImageTexture, SolidTexture, Texture},
+    vec3::Vec3,
+};
+
+// Color given to faces that are not assigned any material
+const DEFAULT_COLOR: Vec3 = Vec3 {
+    x: 0.73,
+    y: 0.73,
+    z: 0.73,
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Texture {
+        path: PathBuf,
+        source: ImageError,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "could not read {}: {source}", path.display()),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+            Self::Texture { path, source } => {
+                write!(f, "could not load texture {}: {source}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { .. } => None,
+            Self::Texture { source, .. } => Some(source),
+        }
+    }
+}
+
+// Triangle mesh built from one group or object of an OBJ file
+pub struct ObjGroup {
+    pub name: String,
+    pub mesh: TriangleMesh,
+}
+
+// Loads every group of a Wavefront OBJ file as a triangle mesh. Polygons are
+// triangulated as fans and the materials of referenced MTL libraries are mapped
+// onto the closest material of the renderer
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Vec<ObjGroup>, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    let mut parser = ObjParser::new(path);
+
+    for (line_index, line) in source.lines().enumerate() {
+        parser.line = line_index + 1;
+        parser.parse_line(line)?;
+    }
+
+    Ok(parser.finish())
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+// Faces collected for a group before its vertex data is compacted into a mesh.
+// Indices refer to the buffers of the whole file
+struct GroupFaces {
+    name: String,
+    faces: Vec<MeshFace>,
+}
+
+struct ObjParser {
+    path: PathBuf,
+    line: usize, // Line currently being parsed, for error reporting
+    directory: PathBuf,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f32, f32)>,
+    material_names: HashMap<String, usize>,
+    materials: Vec<Material>,
+    current_material: Option<usize>,
+    groups: Vec<GroupFaces>,
+}
+
+impl ObjParser {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line: 0,
+            directory: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            material_names: HashMap::new(),
+            materials: Vec::new(),
+            current_material: None,
+            groups: vec![GroupFaces {
+                name: String::from("default"),
+                faces: Vec::new(),
+            }],
+        }
+    }
+
+    fn parse_error(&self, message: String) -> ObjError {
+        ObjError::Parse {
+            path: self.path.clone(),
+            line: self.line,
+            message,
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), ObjError> {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            return Ok(());
+        };
+        let arguments = tokens.collect::<Vec<_>>();
+
+        if keyword == "mtllib" {
+            for library in arguments {
+                let path = self.directory.join(library);
+                self.load_library(&path)?;
+            }
+            return Ok(());
+        }
+
+        self.parse_statement(keyword, &arguments)
+            .map_err(|message| self.parse_error(message))
+    }
+
+    fn parse_statement(&mut self, keyword: &str, arguments: &[&str]) -> Result<(), String> {
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(arguments, 3, "vertex position")?;
+                self.positions.push(Vec3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(arguments, 3, "vertex normal")?;
+                self.normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v] = parse_floats::<2>(arguments, 1, "texture coordinate")?;
+                self.uvs.push((u, v));
+            }
+            "f" => self.parse_face(arguments)?,
+            "g" | "o" => {
+                let name = arguments.join(" ");
+                let current = self.groups.last_mut().expect("parser always has a group");
+                if current.faces.is_empty() {
+                    current.name = name;
+                } else {
+                    self.groups.push(GroupFaces {
+                        name,
+                        faces: Vec::new(),
+                    });
+                }
+            }
+            "usemtl" => {
+                let name = arguments.join(" ");
+                let index = *self
+                    .material_names
+                    .get(&name)
+                    .ok_or_else(|| format!("usemtl references unknown material '{name}'"))?;
+                self.current_material = Some(index);
+            }
+            // Smoothing groups, lines, points and free-form geometry carry nothing
+            // a triangle mesh can use
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn parse_face(&mut self, arguments: &[&str]) -> Result<(), String> {
+        if arguments.len() < 3 {
+            return Err(format!(
+                "face needs at least 3 vertices, found {}",
+                arguments.len()
+            ));
+        }
+
+        let mut positions = Vec::with_capacity(arguments.len());
+        let mut uvs = Vec::with_capacity(arguments.len());
+        let mut normals = Vec::with_capacity(arguments.len());
+        for vertex in arguments {
+            // Vertices are written as v, v/vt, v//vn or v/vt/vn
+            let mut parts = vertex.split('/');
+            let position = parts.next().unwrap_or_default();
+            positions.push(resolve_index(position, self.positions.len(), "vertex")?);
+            uvs.push(match parts.next() {
+                Some(uv) if !uv.is_empty() => {
+                    Some(resolve_index(uv, self.uvs.len(), "texture coordinate")?)
+                }
+                _ => None,
+            });
+            normals.push(match parts.next() {
+                Some(normal) if !normal.is_empty() => {
+                    Some(resolve_index(normal, self.normals.len(), "normal")?)
+                }
+                _ => None,
+            });
+            if parts.next().is_some() {
+                return Err(format!("malformed face vertex '{vertex}'"));
+            }
+        }
+
+        let material = match self.current_material {
+            Some(index) => index,
+            None => self.default_material(),
+        };
+
+        // Triangulate the polygon as a fan around its first vertex. Normals and
+        // texture coordinates are only used when every vertex of a triangle has them
+        let group = self.groups.last_mut().expect("parser always has a group");
+        for i in 1..arguments.len() - 1 {
+            let corners = [0, i, i + 1];
+            group.faces.push(MeshFace {
+                positions: corners.map(|corner| positions[corner]),
+                normals: all_present(corners.map(|corner| normals[corner])),
+                uvs: all_present(corners.map(|corner| uvs[corner])),
+                material: material as u32,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn default_material(&mut self) -> usize {
+        let name = String::new();
+        if let Some(index) = self.material_names.get(&name) {
+            return *index;
+        }
+        self.materials
+            .push(Material::Lambertian(Lambertian::new_from_color(
+                DEFAULT_COLOR,
+            )));
+        self.material_names.insert(name, self.materials.len() - 1);
+        self.materials.len() - 1
+    }
+
+    fn load_library(&mut self, path: &Path) -> Result<(), ObjError> {
+        let source = read_file(path)?;
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut current: Option<(String, MtlEntry)> = None;
+
+        for (line_index, line) in source.lines().enumerate() {
+            let parse_error = |message: String| ObjError::Parse {
+                path: path.to_path_buf(),
+                line: line_index + 1,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            let arguments = tokens.collect::<Vec<_>>();
+
+            if keyword == "newmtl" {
+                if let Some((name, entry)) = current.take() {
+                    self.add_material(name, entry.into_material(&directory)?);
+                }
+                current = Some((arguments.join(" "), MtlEntry::default()));
+                continue;
+            }
+
+            let Some((_, entry)) = current.as_mut() else {
+                if matches!(
+                    keyword,
+                    "Kd" | "Ks" | "Ns" | "Ni" | "d" | "Tr" | "Ke" | "map_Kd"
+                ) {
+                    return Err(parse_error(format!(
+                        "'{keyword}' appears before any newmtl statement"
+                    )));
+                }
+                continue;
+            };
+
+            match keyword {
+                "Kd" => entry.diffuse = parse_color(&arguments).map_err(parse_error)?,
+                "Ks" => entry.specular = parse_color(&arguments).map_err(parse_error)?,
+                "Ke" => entry.emission = parse_color(&arguments).map_err(parse_error)?,
+                "Ns" => {
+                    let [exponent] = parse_floats::<1>(&arguments, 1, "specular exponent")
+                        .map_err(parse_error)?;
+                    entry.specular_exponent = exponent;
+                }
+                "Ni" => {
+                    let [index] = parse_floats::<1>(&arguments, 1, "refractive index")
+                        .map_err(parse_error)?;
+                    entry.refractive_index = Some(index);
+                }
+                "d" => {
+                    let [dissolve] =
+                        parse_floats::<1>(&arguments, 1, "dissolve").map_err(parse_error)?;
+                    entry.dissolve = dissolve;
+                }
+                "Tr" => {
+                    let [transparency] =
+                        parse_floats::<1>(&arguments, 1, "transparency").map_err(parse_error)?;
+                    entry.dissolve = 1. - transparency;
+                }
+                "map_Kd" => {
+                    // Texture options may precede the file name, which comes last
+                    let file = arguments.last().ok_or_else(|| {
+                        parse_error(String::from("map_Kd needs a texture file name"))
+                    })?;
+                    entry.diffuse_map = Some(PathBuf::from(file));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((name, entry)) = current {
+            self.add_material(name, entry.into_material(&directory)?);
+        }
+
+        Ok(())
+    }
+
+    fn add_material(&mut self, name: String, material: Material) {
+        self.materials.push(material);
+        self.material_names.insert(name, self.materials.len() - 1);
+    }
+
+    // Turns every group with faces into a mesh holding only the vertex data and
+    // materials its faces reference
+    fn finish(self) -> Vec<ObjGroup> {
+        self.groups
+            .into_iter()
+            .filter(|group| !group.faces.is_empty())
+            .map(|group| {
+                let mut positions = Compactor::default();
+                let mut normals = Compactor::default();
+                let mut uvs = Compactor::default();
+                let mut materials = Compactor::default();
+
+                let faces = group
+                    .faces
+                    .iter()
+                    .map(|face| MeshFace {
+                        positions: face.positions.map(|i| positions.local(i)),
+                        normals: face
+                            .normals
+                            .map(|indices| indices.map(|i| normals.local(i))),
+                        uvs: face.uvs.map(|indices| indices.map(|i| uvs.local(i))),
+                        material: materials.local(face.material),
+                    })
+                    .collect();
+
+                let mesh = TriangleMesh::new_from_faces(
+                    positions.gather(&self.positions),
+                    normals.gather(&self.normals),
+                    uvs.gather(&self.uvs),
+                    faces,
+                    materials.gather(&self.materials),
+                );
+
+                ObjGroup {
+                    name: group.name,
+                    mesh,
+                }
+            })
+            .collect()
+    }
+}
+
+// Renumbers indices into a file-wide buffer so that they refer to a buffer holding
+// only the referenced elements, in order of first use
+#[derive(Default)]
+struct Compactor {
+    local_indices: HashMap<u32, u32>,
+    used: Vec<u32>,
+}
+
+impl Compactor {
+    fn local(&mut self, index: u32) -> u32 {
+        *self.local_indices.entry(index).or_insert_with(|| {
+            self.used.push(index);
+            self.used.len() as u32 - 1
+        })
+    }
+
+    fn gather<T: Clone>(&self, buffer: &[T]) -> Vec<T> {
+        self.used
+            .iter()
+            .map(|&i| buffer[i as usize].clone())
+            .collect()
+    }
+}
+
+// Properties of a material read from an MTL library
+struct MtlEntry {
+    diffuse: Vec3,
+    specular: Vec3,
+    emission: Vec3,
+    specular_exponent: f32,
+    refractive_index: Option<f32>,
+    dissolve: f32,
+    diffuse_map: Option<PathBuf>,
+}
+
+impl Default for MtlEntry {
+    fn default() -> Self {
+        Self {
+            diffuse: DEFAULT_COLOR,
+            specular: Vec3::ZERO,
+            emission: Vec3::ZERO,
+            specular_exponent: 0.,
+            refractive_index: None,
+            dissolve: 1.,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlEntry {
+    // Emissive entries become lights, transparent ones glass, entries whose specular
+    // color outweighs the diffuse one metals, and everything else diffuse surfaces
+    fn into_material(self, directory: &Path) -> Result<Material, ObjError> {
+        let max_channel = |color: Vec3| color.x.max(color.y).max(color.z);
+
+        if max_channel(self.emission) > 0. {
+            return Ok(Material::DiffuseLight(DiffuseLight::new(Texture::Solid(
+                SolidTexture::new(self.emission),
+            ))));
+        }
+
+        if self.dissolve < 1. {
+            return Ok(Material::Dielectric(Dielectric {
+                refractive_index: self.refractive_index.unwrap_or(1.5),
+            }));
+        }
+
+        if max_channel(self.specular) > max_channel(self.diffuse) {
+            // Map the Phong exponent onto a roughness, sharper highlights being smoother
+            let fuzz = (2. / (self.specular_exponent.max(0.) + 2.)).sqrt();
+            return Ok(Material::Metal(Metal::new(self.specular, fuzz)));
+        }
+
+        let texture = match self.diffuse_map {
+            Some(file) => {
+                let path = directory.join(file);
+                let texture = ImageTexture::new(path.to_string_lossy().into_owned())
+                    .map_err(|source| ObjError::Texture { path, source })?;
+                Texture::Image(texture)
+            }
+            None => Texture::Solid(SolidTexture::new(self.diffuse)),
+        };
+
+        Ok(Material::Lambertian(Lambertian::new(texture)))
+    }
+}
+
+// Resolves a one-based OBJ index, where negative values count back from the most
+// recently defined element, into a zero-based index
+fn resolve_index(token: &str, count: usize, element: &str) -> Result<u32, String> {
+    let index = token
+        .parse::<i64>()
+        .map_err(|_| format!("invalid {element} index '{token}'"))?;
+
+    let resolved = match index {
+        0 => return Err(format!("{element} indices start at 1, found 0")),
+        i if i > 0 => i - 1,
+        i => count as i64 + i,
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{element} index {index} is out of range, only {count} defined so far"
+        ));
+    }
+
+    Ok(resolved as u32)
+}
+
+// Parses between `required` and N numbers, leaving missing optional ones at zero
+fn parse_floats<const N: usize>(
+    arguments: &[&str],
+    required: usize,
+    element: &str,
+) -> Result<[f32; N], String> {
+    if arguments.len() < required {
+        return Err(format!(
+            "{element} needs at least {required} values, found {}",
+            arguments.len()
+        ));
+    }
+
+    let mut values = [0.; N];
+    for (value, token) in values.iter_mut().zip(arguments) {
+        *value = token
+            .parse::<f32>()
+            .map_err(|_| format!("invalid number '{token}' in {element}"))?;
+    }
+
+    Ok(values)
+}
+
+// Parses an "r g b" color, where a single value is used for all three channels
+fn parse_color(arguments: &[&str]) -> Result<Vec3, String> {
+    if arguments.len() == 1 {
+        let [value] = parse_floats::<1>(arguments, 1, "color")?;
+        return Ok(Vec3::splat(value));
+    }
+    let [r, g, b] = parse_floats::<3>(arguments, 3, "color")?;
+    Ok(Vec3::new(r, g, b))
+}
+
+fn all_present(indices: [Option<u32>; 3]) -> Option<[u32; 3]> {
+    match indices {
+        [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parses OBJ statements given inline, as if read from a file with that content
+    fn parse(source: &str) -> Result<Vec<ObjGroup>, ObjError> {
+        let mut parser = ObjParser::new(Path::new("inline.obj"));
+        for (line_index, line) in source.lines().enumerate() {
+            parser.line = line_index + 1;
+            parser.parse_line(line)?;
+        }
+        Ok(parser.finish())
+    }
+
+    #[test]
+    fn polygons_are_triangulated_as_fans() {
+        let groups = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv -1 1 0\n\
+             f 1 2 3 4 5\n",
+        )
+        .unwrap();
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "default");
+        assert_eq!(groups[0].mesh.face_count(), 3);
+    }
+
+    #[test]
+    fn groups_only_keep_their_own_faces() {
+        let groups = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\
+             o first\nf 1 2 3\n\
+             o second\nf 2 4 3\nf -3 -1 -2\n",
+        )
+        .unwrap();
+
+        let summary = groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.mesh.face_count()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, [("first", 1), ("second", 2)]);
+    }
+
+    #[test]
+    fn errors_report_the_line() {
+        let error = parse("v 0 0 0\nv 1 0 0\n\nf 1 2 3\n").err().unwrap();
+        match error {
+            ObjError::Parse { line, message, .. } => {
+                assert_eq!(line, 4);
+                assert!(message.contains("out of range"), "{message}");
+            }
+            error => panic!("expected a parse error, got {error}"),
+        }
+
+        let error = parse("v 0 0 0\nf 0 1 1\n").err().unwrap();
+        assert!(error.to_string().starts_with("inline.obj:2:"), "{error}");
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_element() {
+        assert_eq!(resolve_index("-1", 4, "vertex"), Ok(3));
+        assert_eq!(resolve_index("2", 4, "vertex"), Ok(1));
+        assert!(resolve_index("-5", 4, "vertex").is_err());
+        assert!(resolve_index("x", 4, "vertex").is_err());
+    }
+
+    #[test]
+    fn texture_is_loaded_relative_to_the_library() {
+        let groups = load_obj("tests/fixtures/textured.obj").expect("textured OBJ should load");
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "square");
+        assert_eq!(groups[0].mesh.face_count(), 2);
+    }
+
+    #[test]
+    fn missing_texture_is_reported_next_to_the_library() {
+        let error = match load_obj("tests/fixtures/missing_texture.obj") {
+            Ok(_) => panic!("OBJ with a missing texture should not load"),
+            Err(error) => error,
+        };
+
+        match error {
+            ObjError::Texture { path, .. } => {
+                assert_eq!(path, Path::new("tests/fixtures/missing.png"))
+            }
+            error => panic!("expected a texture error, got {error}"),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::vec3::Vec3;
+
+// Orthonormal basis around a direction, used to carry directions sampled about the
+// z axis over to a surface. `w` is the given direction while `u` and `v` span the
+// plane perpendicular to it
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    // Builds the basis without a helper axis or any square root, following Duff et
+    // al., "Building an Orthonormal Basis, Revisited"
+    pub fn new(normal: Vec3) -> Self {
+        let w = normal.unit();
+        let sign = 1_f32.copysign(w.z);
+        let a = -1. / (sign + w.z);
+        let b = w.x * w.y * a;
+        let u = Vec3::new(1. + sign * w.x * w.x * a, sign * b, -sign * w.x);
+        let v = Vec3::new(b, sign + w.y * w.y * a, -w.y);
+
+        Self { u, v, w }
+    }
+
+    // Direction with the given coordinates along u, v and w
+    pub fn to_world(&self, local: Vec3) -> Vec3 {
+        local.x * self.u + local.y * self.v + local.z * self.w
+    }
+
+    // Coordinates of the direction along u, v and w
+    pub fn to_local(&self, vec: Vec3) -> Vec3 {
+        Vec3::new(
+            Vec3::dot(vec, self.u),
+            Vec3::dot(vec, self.v),
+            Vec3::dot(vec, self.w),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::seeded_rng;
+
+    #[test]
+    fn basis_is_orthonormal_and_right_handed() {
+        let mut rng = seeded_rng(1);
+        let mut normals: Vec<Vec3> = (0..1000)
+            .map(|_| Vec3::random_unit_vector(&mut rng))
+            .collect();
+        // Both poles, where the construction switches sign, and a direction just off
+        // the negative one
+        normals.extend([
+            Vec3::new(0., 0., 1.),
+            Vec3::new(0., 0., -1.),
+            Vec3::new(1e-4, 0., -1.),
+        ]);
+
+        for normal in normals {
+            let onb = Onb::new(3. * normal);
+            assert!((onb.w - normal.unit()).length() < 1e-5);
+            for axis in [onb.u, onb.v, onb.w] {
+                assert!((axis.length() - 1.).abs() < 1e-4, "{normal:?}");
+            }
+            assert!(Vec3::dot(onb.u, onb.v).abs() < 1e-4);
+            assert!(Vec3::dot(onb.u, onb.w).abs() < 1e-4);
+            assert!(Vec3::dot(onb.v, onb.w).abs() < 1e-4);
+            assert!((Vec3::cross(onb.u, onb.v) - onb.w).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn local_coordinates_undo_the_mapping() {
+        let mut rng = seeded_rng(2);
+        for _ in 0..100 {
+            let onb = Onb::new(Vec3::random_unit_vector(&mut rng));
+            let vec = Vec3::random_in_interval(-2., 2., &mut rng);
+            assert!((onb.to_world(onb.to_local(vec)) - vec).length() < 1e-4);
+            assert!((onb.to_local(onb.to_world(vec)) - vec).length() < 1e-4);
+        }
+        let onb = Onb::new(Vec3::new(0., 1., 0.));
+        assert!((onb.to_world(Vec3::new(0., 0., 1.)) - Vec3::new(0., 1., 0.)).length() < 1e-6);
+    }
+}
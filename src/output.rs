@@ -0,0 +1,297 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use exr::prelude::{f16, Image, SpecificChannels, Vec2, WritableImage};
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::{ExtendedColorType, ImageEncoder, Rgb};
+
+use crate::framebuffer::Framebuffer;
+use crate::tonemap::ToneMapping;
+
+// Quality used for JPEG files when the format is picked from the file extension
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+// Encodings a rendered image can be written to. The 8-bit formats store tone
+// mapped colors while the high dynamic range formats (OpenEXR, Radiance and PFM)
+// keep the unclamped linear radiance of the framebuffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg { quality: u8 },     // Quality from 1 (smallest) to 100 (best)
+    PpmBinary,                // P6
+    PpmAscii,                 // P3
+    Exr { half_float: bool }, // 16-bit instead of 32-bit float channels when set
+    Hdr,                      // Radiance RGBE
+    Pfm,                      // Portable float map
+}
+
+impl OutputFormat {
+    // Picks the format matching the extension of the path, ignoring case. PPM files
+    // are written in the binary (P6) flavour
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg {
+                quality: DEFAULT_JPEG_QUALITY,
+            }),
+            "ppm" => Some(Self::PpmBinary),
+            "exr" => Some(Self::Exr { half_float: false }),
+            "hdr" => Some(Self::Hdr),
+            "pfm" => Some(Self::Pfm),
+            _ => None,
+        }
+    }
+
+    // Like from_path, but reports unknown extensions as an error
+    pub(crate) fn from_path_or_err(path: &Path) -> io::Result<Self> {
+        Self::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported image extension: {}", path.display()),
+            )
+        })
+    }
+
+    // Writes the framebuffer to the path, using the tone mapping only for 8-bit formats
+    pub fn write(
+        &self,
+        buffer: &Framebuffer,
+        tone_mapping: &ToneMapping,
+        path: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        match self {
+            Self::Png => {
+                image::codecs::png::PngEncoder::new(&mut file)
+                    .write_image(
+                        &buffer.to_rgb8(tone_mapping),
+                        buffer.width,
+                        buffer.height,
+                        ExtendedColorType::Rgb8,
+                    )
+                    .map_err(io::Error::other)?;
+            }
+            Self::Jpeg { quality } => {
+                JpegEncoder::new_with_quality(&mut file, (*quality).clamp(1, 100))
+                    .write_image(
+                        &buffer.to_rgb8(tone_mapping),
+                        buffer.width,
+                        buffer.height,
+                        ExtendedColorType::Rgb8,
+                    )
+                    .map_err(io::Error::other)?;
+            }
+            Self::PpmBinary => {
+                file.write_all(
+                    format!("P6\n{} {}\n255\n", buffer.width, buffer.height).as_bytes(),
+                )?;
+                file.write_all(&buffer.to_rgb8(tone_mapping))?;
+            }
+            Self::PpmAscii => {
+                file.write_all(
+                    format!("P3\n{} {}\n255\n", buffer.width, buffer.height).as_bytes(),
+                )?;
+                for color in buffer.pixels.iter() {
+                    let [r, g, b] = tone_mapping.to_bytes(*color);
+                    file.write_all(format!("{} {} {}\n", r, g, b).as_bytes())?;
+                }
+            }
+            Self::Exr { half_float } => {
+                let (width, height) = (buffer.width as usize, buffer.height as usize);
+                let color_at = |x: usize, y: usize| buffer.pixels[y * width + x];
+                let result = if *half_float {
+                    let channels = SpecificChannels::rgb(|Vec2(x, y)| {
+                        let color = color_at(x, y);
+                        (
+                            f16::from_f32(color.x),
+                            f16::from_f32(color.y),
+                            f16::from_f32(color.z),
+                        )
+                    });
+                    Image::from_channels((width, height), channels)
+                        .write()
+                        .to_buffered(&mut file)
+                } else {
+                    let channels = SpecificChannels::rgb(|Vec2(x, y)| {
+                        let color = color_at(x, y);
+                        (color.x, color.y, color.z)
+                    });
+                    Image::from_channels((width, height), channels)
+                        .write()
+                        .to_buffered(&mut file)
+                };
+                result.map_err(io::Error::other)?;
+            }
+            Self::Hdr => {
+                let pixels = buffer
+                    .pixels
+                    .iter()
+                    .map(|color| Rgb([color.x, color.y, color.z]))
+                    .collect::<Vec<_>>();
+                HdrEncoder::new(&mut file)
+                    .encode(&pixels, buffer.width as usize, buffer.height as usize)
+                    .map_err(io::Error::other)?;
+            }
+            Self::Pfm => {
+                // A negative scale marks little-endian samples, and rows are stored
+                // from the bottom of the image up
+                file.write_all(
+                    format!("PF\n{} {}\n-1.0\n", buffer.width, buffer.height).as_bytes(),
+                )?;
+                for row in buffer.pixels.chunks(buffer.width.max(1) as usize).rev() {
+                    for color in row {
+                        for channel in [color.x, color.y, color.z] {
+                            file.write_all(&channel.to_le_bytes())?;
+                        }
+                    }
+                }
+            }
+        }
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::vec3::Vec3;
+
+    fn gradient() -> Framebuffer {
+        let pixels = (0..6)
+            .map(|i| Vec3::new(i as f32 / 5., 1. - i as f32 / 5., 0.5))
+            .collect();
+        Framebuffer::new_from_pixels(3, 2, pixels)
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("output-{}-{name}", std::process::id()))
+    }
+
+    // Writes the image, decodes it again and removes the file
+    fn round_trip(format: OutputFormat, name: &str) -> image::RgbImage {
+        let path = temp_path(name);
+        format
+            .write(&gradient(), &ToneMapping::default(), &path)
+            .unwrap();
+        let decoded = image::open(&path).unwrap().to_rgb8();
+        let _ = fs::remove_file(&path);
+        decoded
+    }
+
+    #[test]
+    fn format_follows_the_extension() {
+        assert_eq!(OutputFormat::from_path("a.PNG"), Some(OutputFormat::Png));
+        assert_eq!(
+            OutputFormat::from_path("a.ppm"),
+            Some(OutputFormat::PpmBinary)
+        );
+        assert_eq!(
+            OutputFormat::from_path("a.jpeg"),
+            Some(OutputFormat::Jpeg {
+                quality: DEFAULT_JPEG_QUALITY
+            })
+        );
+        assert_eq!(OutputFormat::from_path("a.bmp"), None);
+        assert_eq!(OutputFormat::from_path("a"), None);
+    }
+
+    #[test]
+    fn lossless_formats_round_trip() {
+        let expected = gradient().to_rgb8(&ToneMapping::default());
+        for (format, name) in [
+            (OutputFormat::Png, "image.png"),
+            (OutputFormat::PpmBinary, "binary.ppm"),
+            (OutputFormat::PpmAscii, "ascii.ppm"),
+        ] {
+            let decoded = round_trip(format, name);
+            assert_eq!(decoded.dimensions(), (3, 2), "{format:?}");
+            assert_eq!(decoded.into_raw(), expected, "{format:?}");
+        }
+    }
+
+    // Unclamped colors well above one, which 8-bit formats would lose
+    fn radiance() -> Framebuffer {
+        let pixels = (0..6)
+            .map(|i| Vec3::new(0.125 * i as f32, 4. + i as f32, 100. / (i + 1) as f32))
+            .collect();
+        Framebuffer::new_from_pixels(3, 2, pixels)
+    }
+
+    // Writes the radiance image, decodes it as floats and removes the file
+    fn float_round_trip(format: OutputFormat, name: &str) -> Vec<f32> {
+        let path = temp_path(name);
+        format
+            .write(&radiance(), &ToneMapping::default(), &path)
+            .unwrap();
+        let decoded = image::open(&path).unwrap().to_rgb32f();
+        let _ = fs::remove_file(&path);
+        assert_eq!(decoded.dimensions(), (3, 2), "{format:?}");
+        decoded.into_raw()
+    }
+
+    fn channels(image: &Framebuffer) -> Vec<f32> {
+        image
+            .pixels
+            .iter()
+            .flat_map(|color| [color.x, color.y, color.z])
+            .collect()
+    }
+
+    #[test]
+    fn float_formats_keep_radiance_above_one() {
+        let expected = channels(&radiance());
+        let within = |decoded: Vec<f32>, tolerance: f32| {
+            decoded
+                .iter()
+                .zip(&expected)
+                .all(|(value, expected)| (value - expected).abs() <= tolerance * expected.max(1.))
+        };
+
+        let exr = float_round_trip(OutputFormat::Exr { half_float: false }, "full.exr");
+        assert_eq!(exr, expected);
+        let half = float_round_trip(OutputFormat::Exr { half_float: true }, "half.exr");
+        assert!(within(half, 1e-3));
+        // RGBE shares one exponent and 8-bit mantissas between the channels, so the
+        // error is relative to the brightest channel of each pixel
+        let hdr = float_round_trip(OutputFormat::Hdr, "image.hdr");
+        for (decoded, expected) in hdr.chunks(3).zip(expected.chunks(3)) {
+            let brightest = expected.iter().cloned().fold(0., f32::max);
+            for (value, expected) in decoded.iter().zip(expected) {
+                assert!((value - expected).abs() <= brightest / 128.);
+            }
+        }
+    }
+
+    #[test]
+    fn pfm_stores_rows_bottom_up() {
+        let path = temp_path("image.pfm");
+        OutputFormat::Pfm
+            .write(&radiance(), &ToneMapping::default(), &path)
+            .unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let values = bytes[header.len()..]
+            .chunks(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        let expected = channels(&radiance());
+        assert_eq!(values[..9], expected[9..]);
+        assert_eq!(values[9..], expected[..9]);
+    }
+
+    #[test]
+    fn jpeg_keeps_the_size() {
+        let decoded = round_trip(OutputFormat::Jpeg { quality: 100 }, "image.jpg");
+        assert_eq!(decoded.dimensions(), (3, 2));
+    }
+}
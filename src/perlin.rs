@@ -1,21 +1,27 @@
 use itertools::Itertools;
 use rand::prelude::*;
 
-use crate::{utilities::random_num, vec3::Vec3};
+use crate::utilities::seeded_rng;
+use crate::vec3::Vec3;
 
 #[derive(Clone)]
 pub struct Perlin {
     permutation_x: [usize; 256],
     permutation_y: [usize; 256],
     permutation_z: [usize; 256],
-    random_array: [f32; 256],
     random_vectors: [Vec3; 256],
 }
 
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Perlin {
-    fn permute(mut point: [usize; 256], swaps: usize) -> [usize; 256] {
+    fn permute<R: Rng + ?Sized>(mut point: [usize; 256], swaps: usize, rng: &mut R) -> [usize; 256] {
         for i in (0..swaps).rev() {
-            let draw = rand::thread_rng().gen_range(0..(i + 1));
+            let draw = rng.gen_range(0..(i + 1));
             point.swap(i,draw);
 
         }
@@ -23,26 +29,33 @@ impl Perlin {
         point
     }
 
-    fn generate_permutation() -> [usize; 256] {
+    fn generate_permutation<R: Rng + ?Sized>(rng: &mut R) -> [usize; 256] {
         let point: [usize; 256] = core::array::from_fn(|i| i);
 
-        Self::permute(point, 256)
+        Self::permute(point, 256, rng)
     }
 
+    // Noise with a freshly drawn random lattice, different on every call
     pub fn new() -> Perlin {
-        let random_array: [f32; 256] = core::array::from_fn(|_| random_num());
-        let random_vectors = core::array::from_fn(|_| Vec3::random());
+        Self::new_from_rng(&mut thread_rng())
+    }
+
+    // Noise whose lattice is fully determined by the seed
+    pub fn new_from_seed(seed: u64) -> Perlin {
+        Self::new_from_rng(&mut seeded_rng(seed))
+    }
 
+    pub fn new_from_rng<R: Rng + ?Sized>(rng: &mut R) -> Perlin {
+        let random_vectors = core::array::from_fn(|_| Vec3::random(rng));
 
-        let permutation_x = Self::generate_permutation();
-        let permutation_y = Self::generate_permutation();
-        let permutation_z = Self::generate_permutation();
+        let permutation_x = Self::generate_permutation(rng);
+        let permutation_y = Self::generate_permutation(rng);
+        let permutation_z = Self::generate_permutation(rng);
 
         Self {
             permutation_x,
             permutation_y,
             permutation_z,
-            random_array,
             random_vectors,
         }
     }
@@ -63,8 +76,7 @@ impl Perlin {
             c[di][dj][dk] = self.random_vectors[self.permutation_x[((i + di as isize) & 255) as usize]^self.permutation_y[((j + dj as isize) & 255) as usize]^self.permutation_z[((k + dk as isize) & 255) as usize]]
         }
 
-        let res = Self::perlin_interpolate(c, u, v, w);
-        res
+        Self::perlin_interpolate(c, u, v, w)
     }
 
     fn perlin_interpolate(c: [[[Vec3; 2]; 2]; 2], u: f32, v: f32, w: f32) -> f32 {
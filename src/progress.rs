@@ -0,0 +1,211 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use indicatif::ProgressBar;
+
+// How far a render has got. Totals are unknown for progressive renders running
+// until their time budget is spent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub pixels_done: u64,
+    pub pixels_total: Option<u64>,
+    pub tiles_done: u64,
+    pub tiles_total: Option<u64>,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    // Share of the render done so far, between 0 and 1
+    pub fn fraction(&self) -> Option<f32> {
+        let total = self.pixels_total?;
+        if total == 0 {
+            return Some(1.);
+        }
+        Some(self.pixels_done as f32 / total as f32)
+    }
+
+    // Time left assuming the rest of the render goes as fast as the part done so far
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction()?;
+        if fraction <= 0. {
+            return None;
+        }
+        Some(self.elapsed.mul_f32((1. - fraction) / fraction))
+    }
+}
+
+// Receives the progress of a render after every finished tile. Reports come from the
+// render threads, so they should return quickly
+pub trait ProgressReporter: Sync {
+    fn report(&self, progress: &Progress);
+
+    // Called once the render is over, whether it finished or was cancelled
+    fn finish(&self, _progress: &Progress) {}
+}
+
+// Reporter ignoring all progress, for renders that should stay silent
+pub struct Quiet;
+
+impl ProgressReporter for Quiet {
+    fn report(&self, _progress: &Progress) {}
+}
+
+// Any closure taking the progress can be used as a reporter
+impl<F: Fn(&Progress) + Sync> ProgressReporter for F {
+    fn report(&self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+// Terminal progress bar showing the pixels rendered so far
+impl ProgressReporter for ProgressBar {
+    fn report(&self, progress: &Progress) {
+        if let Some(total) = progress.pixels_total {
+            self.set_length(total);
+        }
+        self.set_position(progress.pixels_done);
+    }
+
+    fn finish(&self, progress: &Progress) {
+        self.report(progress);
+        ProgressBar::finish(self);
+    }
+}
+
+// Shared flag asking a render to stop. Clones refer to the same flag, so one can be
+// handed to the render while another is kept to cancel it from a different thread.
+// Renders check it before starting every tile
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// Counts the tiles finished by the render threads and passes the progress on to
+// the reporter
+pub(crate) struct Tracker<'a> {
+    reporter: &'a dyn ProgressReporter,
+    cancellation: &'a CancellationToken,
+    start: Instant,
+    pixels_done: AtomicU64,
+    tiles_done: AtomicU64,
+    pixels_total: Option<u64>,
+    tiles_total: Option<u64>,
+}
+
+impl<'a> Tracker<'a> {
+    pub(crate) fn new(
+        reporter: &'a dyn ProgressReporter,
+        cancellation: &'a CancellationToken,
+        pixels_total: Option<u64>,
+        tiles_total: Option<u64>,
+    ) -> Self {
+        Self {
+            reporter,
+            cancellation,
+            start: Instant::now(),
+            pixels_done: AtomicU64::new(0),
+            tiles_done: AtomicU64::new(0),
+            pixels_total,
+            tiles_total,
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    // Reports the counts as they stood right after adding this tile, taken from the
+    // update itself rather than read again once other threads may have moved them on
+    pub(crate) fn tile_finished(&self, pixels: u64) {
+        let pixels_done = self.pixels_done.fetch_add(pixels, Ordering::Relaxed) + pixels;
+        let tiles_done = self.tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+        let progress = self.progress(pixels_done, tiles_done);
+        self.reporter.report(&progress);
+    }
+
+    pub(crate) fn finish(&self) {
+        let pixels_done = self.pixels_done.load(Ordering::Relaxed);
+        let tiles_done = self.tiles_done.load(Ordering::Relaxed);
+        let progress = self.progress(pixels_done, tiles_done);
+        self.reporter.finish(&progress);
+    }
+
+    fn progress(&self, pixels_done: u64, tiles_done: u64) -> Progress {
+        Progress {
+            pixels_done,
+            pixels_total: self.pixels_total,
+            tiles_done,
+            tiles_total: self.tiles_total,
+            elapsed: self.start.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn progress(pixels_done: u64, pixels_total: Option<u64>) -> Progress {
+        Progress {
+            pixels_done,
+            pixels_total,
+            tiles_done: 0,
+            tiles_total: None,
+            elapsed: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn eta_follows_the_pace_so_far() {
+        let quarter = progress(25, Some(100));
+        assert_eq!(quarter.fraction(), Some(0.25));
+        assert_eq!(quarter.eta(), Some(Duration::from_secs(30)));
+
+        assert_eq!(progress(0, Some(100)).eta(), None);
+        assert_eq!(progress(0, Some(0)).fraction(), Some(1.));
+        assert_eq!(progress(50, None).fraction(), None);
+    }
+
+    #[test]
+    fn clones_of_a_token_share_the_cancellation() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn tracker_reports_the_counts_after_every_tile() {
+        let reports = Mutex::new(Vec::new());
+        let report = |progress: &Progress| {
+            let mut reports = reports.lock().unwrap();
+            reports.push((progress.pixels_done, progress.tiles_done));
+        };
+        let cancellation = CancellationToken::new();
+        let tracker = Tracker::new(&report, &cancellation, Some(20), Some(2));
+
+        tracker.tile_finished(16);
+        tracker.tile_finished(4);
+
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports, [(16, 1), (20, 2)]);
+    }
+}
@@ -0,0 +1,266 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::filter::FilmTile;
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Vec3;
+
+// Smallest sum of filter weights a pixel is normalised by. A sample at the centre of a
+// pixel weighs about one with every filter
+const MIN_WEIGHT: f32 = 1e-3;
+
+// How often a progressive render writes the image accumulated so far
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotInterval {
+    Passes(u32),    // After every given number of passes
+    Time(Duration), // After the first pass finishing once the given time has passed
+}
+
+// Settings of a progressive render, which takes a few samples of every pixel in each
+// pass over the image and adds them to the ones of the previous passes. The render
+// stops once it reaches `target_samples` per pixel or runs out of `time_budget`,
+// whichever comes first. Without either it stops at the samples per pixel of the
+// camera. With `resume` set, a render carries on from the checkpoint left by an
+// earlier run with the same settings and ends with the same image as if it had run
+// uninterrupted, while a missing checkpoint simply starts the render afresh. A
+// render that stopped at a target that is not a whole number of passes cannot be
+// carried on any further
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progressive {
+    pub samples_per_pass: u32,
+    pub target_samples: Option<u32>,
+    pub time_budget: Option<Duration>, // Checked after every pass, so the last one may overrun it
+    pub snapshot_path: Option<PathBuf>, // Image written as passes finish, in the format of its extension
+    pub snapshot_interval: SnapshotInterval,
+    pub checkpoint_path: Option<PathBuf>,
+    pub checkpoint_interval: SnapshotInterval,
+    pub resume: bool,
+}
+
+impl Default for Progressive {
+    fn default() -> Self {
+        Self {
+            samples_per_pass: 1,
+            target_samples: None,
+            time_budget: None,
+            snapshot_path: None,
+            snapshot_interval: SnapshotInterval::Passes(1),
+            checkpoint_path: None,
+            checkpoint_interval: SnapshotInterval::Passes(1),
+            resume: false,
+        }
+    }
+}
+
+impl Progressive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Values below one are treated as one
+    pub fn with_samples_per_pass(mut self, samples: u32) -> Self {
+        self.samples_per_pass = samples.max(1);
+        self
+    }
+
+    pub fn with_target_samples(mut self, samples: u32) -> Self {
+        self.target_samples = Some(samples);
+        self
+    }
+
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    pub fn with_snapshots(mut self, path: impl Into<PathBuf>, interval: SnapshotInterval) -> Self {
+        self.snapshot_path = Some(path.into());
+        self.snapshot_interval = interval;
+        self
+    }
+
+    pub fn with_checkpoints(
+        mut self,
+        path: impl Into<PathBuf>,
+        interval: SnapshotInterval,
+    ) -> Self {
+        self.checkpoint_path = Some(path.into());
+        self.checkpoint_interval = interval;
+        self
+    }
+
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    // Samples of every pixel taken in each pass. The field is public, so values below
+    // one are treated as one here as well
+    pub(crate) fn pass_samples(&self) -> u32 {
+        self.samples_per_pass.max(1)
+    }
+}
+
+// Keeps track of when the next write at a snapshot interval is due
+pub(crate) struct Schedule {
+    interval: SnapshotInterval,
+    passes: u32,
+    last: Instant,
+}
+
+impl Schedule {
+    pub(crate) fn new(interval: SnapshotInterval) -> Self {
+        Self {
+            interval,
+            passes: 0,
+            last: Instant::now(),
+        }
+    }
+
+    // Counts a finished pass and tells whether a write is due, starting the next
+    // interval if it is
+    pub(crate) fn pass_finished(&mut self) -> bool {
+        self.passes += 1;
+        let due = match self.interval {
+            SnapshotInterval::Passes(passes) => self.passes >= passes,
+            SnapshotInterval::Time(interval) => self.last.elapsed() >= interval,
+        };
+        if due {
+            self.passes = 0;
+            self.last = Instant::now();
+        }
+        due
+    }
+}
+
+// Path next to the given one that a file is written to before being moved into place
+pub(crate) fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+// Sums of the radiance of every sample taken so far for each pixel, weighted by the
+// reconstruction filter, together with the sums of the weights, row-major. Every
+// pass samples the whole image, so all pixels share the same number of samples
+#[derive(Clone, Debug, Default)]
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+    pub sums: Vec<Vec3>,
+    pub weights: Vec<f32>,
+}
+
+impl Accumulator {
+    // Accumulator holding no samples yet
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Self {
+            width,
+            height,
+            samples: 0,
+            sums: vec![Vec3::ZERO; size],
+            weights: vec![0.; size],
+        }
+    }
+
+    // Adds the weighted sums splatted by a tile. Tiles overlap where the filter
+    // reaches past their edges, so they need to be added in the same order every
+    // time for the sums to come out the same
+    pub(crate) fn add_tile(&mut self, tile: &FilmTile) {
+        for row in 0..tile.height {
+            let start = ((tile.y0 + row) * self.width + tile.x0) as usize;
+            let tile_start = (row * tile.width) as usize;
+            let range = tile_start..tile_start + tile.width as usize;
+            let sums = self.sums[start..].iter_mut().zip(&tile.sums[range.clone()]);
+            for (sum, tile_sum) in sums {
+                *sum += *tile_sum;
+            }
+            let weights = self.weights[start..].iter_mut().zip(&tile.weights[range]);
+            for (weight, tile_weight) in weights {
+                *weight += *tile_weight;
+            }
+        }
+    }
+
+    // Filtered radiance of every pixel. Filters with negative lobes can leave the
+    // weights of a pixel summing to nearly nothing or less, where dividing by them
+    // would blow the pixel up to huge or NaN values, so such pixels stay black like
+    // the ones no sample reaches
+    pub fn framebuffer(&self) -> Framebuffer {
+        let pixels = self
+            .sums
+            .iter()
+            .zip(&self.weights)
+            .map(|(sum, weight)| {
+                if *weight < MIN_WEIGHT {
+                    Vec3::ZERO
+                } else {
+                    (1. / weight) * *sum
+                }
+            })
+            .collect();
+        Framebuffer::new_from_pixels(self.width, self.height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+
+    #[test]
+    fn overlapping_tiles_add_up() {
+        // A tent wider than a pixel reaches the neighbouring pixel, which sits in the
+        // other tile
+        let filter = Filter::new_tent(1.5);
+        let mut accumulator = Accumulator::new(2, 1);
+        assert_eq!(accumulator.framebuffer().get(1, 0).x, 0.);
+
+        let mut left = FilmTile::new(filter, (0, 0, 1, 1), 2, 1);
+        left.add_sample(0, 0, (0., 0.), Vec3::splat(1.));
+        let mut right = FilmTile::new(filter, (1, 0, 2, 1), 2, 1);
+        right.add_sample(1, 0, (0., 0.), Vec3::splat(4.));
+        assert_eq!((left.width, right.x0), (2, 0));
+        accumulator.add_tile(&left);
+        accumulator.add_tile(&right);
+
+        let image = accumulator.framebuffer();
+        assert!((image.get(0, 0).y - 1.75).abs() < 1e-5);
+        assert!((image.get(1, 0).y - 3.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn pixels_without_enough_weight_stay_black() {
+        let accumulator = Accumulator {
+            width: 3,
+            height: 1,
+            samples: 1,
+            sums: vec![Vec3::splat(1.), Vec3::splat(1.), Vec3::splat(4.)],
+            weights: vec![1e-4, -0.5, 2.],
+        };
+
+        let image = accumulator.framebuffer();
+        assert_eq!(image.get(0, 0).x, 0.);
+        assert_eq!(image.get(1, 0).x, 0.);
+        assert_eq!(image.get(2, 0).x, 2.);
+    }
+
+    #[test]
+    fn passes_take_at_least_one_sample() {
+        assert_eq!(
+            Progressive::new().with_samples_per_pass(0).pass_samples(),
+            1
+        );
+        let mut progressive = Progressive::new();
+        progressive.samples_per_pass = 0;
+        assert_eq!(progressive.pass_samples(), 1);
+    }
+
+    #[test]
+    fn partial_files_sit_next_to_the_target() {
+        let partial = partial_path(Path::new("renders/out.png"));
+        assert_eq!(partial, Path::new("renders/out.png.partial"));
+    }
+}
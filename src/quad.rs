@@ -1,5 +1,5 @@
 use crate::{
-    aabb::Aabb, hittable::{HitData, Hittable}, interval::Interval, material::Material, ray::Ray, vec3::Vec3
+    aabb::Aabb, hittable::{HitData, Hittable}, interval::Interval, material::Material, ray::Ray, utilities::RenderRng, vec3::Vec3
 };
 
 pub struct Quad {
@@ -43,7 +43,13 @@ impl Quad {
 }
 
 impl Hittable for Quad {
-    fn hit(&self, ray: Ray, interval: Interval, hit_data: &mut HitData) -> bool {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        _rng: &mut RenderRng,
+    ) -> bool {
         // Calculation to see where ray intersects the plane containing the quad
         let denominator = Vec3::dot(self.unscaled_normal, ray.direction);
 
@@ -70,7 +76,7 @@ impl Hittable for Quad {
         hit_data.hit_along_ray = t_intersection;
         hit_data.point = point_intersection;
         hit_data.material = Some(self.material.clone());
-        hit_data.set_face_normal(ray, self.unscaled_normal);
+        hit_data.set_face_normal(ray, self.normal);
         hit_data.u = alpha;
         hit_data.v = beta;
 
@@ -80,4 +86,43 @@ impl Hittable for Quad {
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
+
+    fn pdf_value(&self, ray: Ray, rng: &mut RenderRng) -> f32 {
+        let mut hit_data = HitData::default();
+        if !self.hit(ray, Interval::new(0.001, f32::INFINITY), &mut hit_data, rng) {
+            return 0.;
+        }
+
+        // Points are drawn uniformly over the area, so the density per solid angle
+        // grows with the distance and the obliqueness of the quad seen from the origin
+        let distance_squared = hit_data.hit_along_ray * hit_data.hit_along_ray * ray.direction.length_squared();
+        let cosine = (Vec3::dot(ray.direction, self.normal) / ray.direction.length()).abs();
+        distance_squared / (cosine * self.unscaled_normal.length())
+    }
+
+    fn random(&self, origin: Vec3, _time: f32, sample: (f32, f32)) -> Vec3 {
+        let point = self.corner + sample.0 * self.first_vector + sample.1 * self.second_vector;
+        point - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::utilities::seeded_rng;
+
+    #[test]
+    fn hit_normal_has_unit_length() {
+        // Edges longer than one give an unscaled normal far from unit length
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.5, 0.5, 0.5)));
+        let quad = Quad::new(Vec3::new(-2., -2., -1.), Vec3::new(4., 0., 0.), Vec3::new(0., 4., 0.), material);
+        let ray = Ray::new(Vec3::ZERO, Vec3::new(0., 0., -1.), 0.);
+        let mut hit_data = HitData::default();
+        let mut rng = seeded_rng(0);
+
+        assert!(quad.hit(ray, Interval::new(0.001, f32::INFINITY), &mut hit_data, &mut rng));
+        assert!((hit_data.normal.length() - 1.).abs() < 1e-6);
+        assert!(hit_data.front_face);
+    }
 }
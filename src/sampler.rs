@@ -0,0 +1,538 @@
+use std::sync::OnceLock;
+
+use crate::utilities::{mix_seed, random_num, seeded_rng, RenderRng};
+
+// Bases of the Halton sequence, one per dimension. Dimensions past these fall back to
+// independent random numbers
+const HALTON_PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+// Number of dimensions the Sobol sequence is defined for. Every further run of this
+// many dimensions reuses them with a scrambling of its own
+const SOBOL_DIMENSIONS: usize = 4;
+
+// Generator matrices of the first Sobol dimensions, one 32-bit direction number per
+// bit of the sample index
+const SOBOL_MATRICES: [[u32; 32]; SOBOL_DIMENSIONS] = sobol_matrices();
+
+// Side length of the tileable blue noise mask dithering the blue noise sampler
+const BLUE_NOISE_SIZE: usize = 64;
+
+// Standard deviation in pixels of the Gaussian weighing how clustered the points of
+// the mask are while it is built
+const BLUE_NOISE_SIGMA: f32 = 1.9;
+
+// Seed of the initial random pattern of the mask, which is the same for every render
+const BLUE_NOISE_SEED: u64 = 0x5EED_B1E0;
+
+// How the sample points driving the random decisions along a path are chosen. Apart
+// from the independent sampler they spread the samples of every pixel more evenly
+// than random numbers do, which lowers the noise at the same sample count
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Sampler {
+    // Uniform random numbers for every dimension
+    #[default]
+    Independent,
+    // Jittered grid over the samples of a pixel in every pair of dimensions, with the
+    // strata shuffled independently for each pair
+    Stratified,
+    // Halton sequence with the digits scrambled differently in every pixel
+    Halton,
+    // Sobol sequence with hash-based Owen scrambling different in every pixel
+    Sobol,
+    // One Sobol sequence for the whole image, shifted in every pixel by a blue noise
+    // mask so that the remaining error looks like fine grain rather than blotches.
+    // Its points are shuffled differently for every run of Sobol dimensions, so that
+    // the runs reusing the same dimensions are not correlated with each other
+    BlueNoise,
+}
+
+impl Sampler {
+    // Sample points for the given sample of the pixel at (x, y), out of the number of
+    // samples the pixel is expected to take
+    pub fn start(
+        &self,
+        seed: u64,
+        x: u32,
+        y: u32,
+        sample: u32,
+        samples_per_pixel: u32,
+    ) -> PixelSample {
+        PixelSample {
+            sampler: *self,
+            seed,
+            pixel_seed: mix_seed(mix_seed(seed) ^ ((y as u64) << 32 | x as u64)),
+            x,
+            y,
+            index: sample,
+            count: samples_per_pixel.max(1),
+            dimension: 0,
+        }
+    }
+}
+
+// Dimensions of one sample of one pixel, handed out in the order the camera consumes
+// them: the position in the pixel, the point on the lens, the time and then the
+// scattering and light sample points of every bounce. Dimensions a sampler does not
+// cover are drawn from the random number generator of the sample
+pub struct PixelSample {
+    sampler: Sampler,
+    seed: u64,
+    pixel_seed: u64,
+    x: u32,
+    y: u32,
+    index: u32,
+    count: u32,
+    dimension: u32,
+}
+
+impl PixelSample {
+    pub fn next_1d(&mut self, rng: &mut RenderRng) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        match self.sampler {
+            Sampler::Independent => random_num(rng),
+            Sampler::Stratified => {
+                let (stratum, pattern) = self.stratum(dimension);
+                let permuted = permute(stratum, self.count, pattern);
+                (permuted as f32 + jitter(stratum, pattern ^ 0x967A_889B)) / self.count as f32
+            }
+            Sampler::Halton => match HALTON_PRIMES.get(dimension as usize) {
+                Some(&base) => scrambled_radical_inverse(
+                    base,
+                    self.index,
+                    hash(self.pixel_seed, dimension as u64),
+                ),
+                None => random_num(rng),
+            },
+            Sampler::Sobol => scrambled_sobol(self.index, dimension, self.pixel_seed),
+            Sampler::BlueNoise => {
+                let value = scrambled_sobol(self.index, dimension, self.seed);
+                let shifted = value + blue_noise(self.x, self.y, dimension);
+                shifted.fract().min(1. - f32::EPSILON)
+            }
+        }
+    }
+
+    // Pairs start on an even dimension so that they never straddle two runs of Sobol
+    // dimensions
+    pub fn next_2d(&mut self, rng: &mut RenderRng) -> (f32, f32) {
+        self.dimension += self.dimension % 2;
+
+        if self.sampler == Sampler::Stratified {
+            let (stratum, pattern) = self.stratum(self.dimension);
+            self.dimension += 2;
+            return correlated_multi_jitter(stratum, self.count, pattern);
+        }
+        (self.next_1d(rng), self.next_1d(rng))
+    }
+
+    // Index of the sample among the strata of the pixel and the shuffling pattern of
+    // the dimension. Samples past the expected count start a new round of strata
+    // shuffled another way
+    fn stratum(&self, dimension: u32) -> (u32, u32) {
+        let round = self.index / self.count;
+        let pattern = hash(self.pixel_seed, (round as u64) << 32 | dimension as u64);
+        (self.index % self.count, pattern)
+    }
+}
+
+// Hashes a value together with a seed into 32 well mixed bits
+fn hash(seed: u64, value: u64) -> u32 {
+    (mix_seed(seed ^ mix_seed(value)) >> 32) as u32
+}
+
+// Maps 32 random bits to [0,1) keeping only the bits an f32 can represent
+fn unit_float(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+// Pseudo-random permutation of [0, length) chosen by the pattern, from Kensler,
+// "Correlated Multi-Jittered Sampling". Hashes within the next power of two and
+// walks the cycle until landing back inside the range
+fn permute(mut i: u32, length: u32, pattern: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    loop {
+        i ^= pattern;
+        i = i.wrapping_mul(0xE170_893D);
+        i ^= pattern >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= pattern >> 8;
+        i = i.wrapping_mul(0x0929_EB3F);
+        i ^= pattern >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | pattern >> 27);
+        i = i.wrapping_mul(0x6935_FA69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74DC_B303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9E50_1CC3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xC860_A3DF);
+        i &= mask;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    (i + pattern % length) % length
+}
+
+// Offset within a stratum, from the same paper
+fn jitter(mut i: u32, pattern: u32) -> f32 {
+    i ^= pattern;
+    i ^= i >> 17;
+    i ^= i >> 10;
+    i = i.wrapping_mul(0xB365_34E5);
+    i ^= i >> 12;
+    i ^= i >> 21;
+    i = i.wrapping_mul(0x93FC_4795);
+    i ^= 0xDF6E_307F;
+    i ^= i >> 17;
+    i = i.wrapping_mul(1 | pattern >> 18);
+    unit_float(i)
+}
+
+// Sample of a jittered grid for any number of samples. The unit square is split into
+// as many rows as samples and into roughly square cells, and every sample lands in a
+// different row and a different column of cells
+fn correlated_multi_jitter(sample: u32, count: u32, pattern: u32) -> (f32, f32) {
+    let columns = ((count as f32).sqrt() as u32).max(1);
+    let rows = count.div_ceil(columns);
+    let sample = permute(sample, count, pattern.wrapping_mul(0x5163_3E2D));
+    let column = permute(sample % columns, columns, pattern.wrapping_mul(0x68BC_21EB));
+    let row = permute(sample / columns, rows, pattern.wrapping_mul(0x02E5_BE93));
+    let jitter_x = jitter(sample, pattern.wrapping_mul(0x967A_889B));
+    let jitter_y = jitter(sample, pattern.wrapping_mul(0x368C_C8B7));
+
+    let x = (column as f32 + (row as f32 + jitter_x) / rows as f32) / columns as f32;
+    let y = (sample as f32 + jitter_y) / count as f32;
+    (x.min(1. - f32::EPSILON), y.min(1. - f32::EPSILON))
+}
+
+// Digits of the index in the given base mirrored around the radix point, with every
+// digit position shuffled by its own permutation. The permuted trailing zeros are
+// kept until they no longer affect an f32
+fn scrambled_radical_inverse(base: u32, mut index: u32, seed: u32) -> f32 {
+    let inverse_base = 1. / base as f64;
+    let mut weight = inverse_base;
+    let mut value = 0.;
+    let mut position = 0;
+
+    while weight > 1e-9 {
+        let digit = index % base;
+        let pattern = hash(seed as u64, position);
+        value += permute(digit, base, pattern) as f64 * weight;
+        index /= base;
+        weight *= inverse_base;
+        position += 1;
+    }
+    (value as f32).min(1. - f32::EPSILON)
+}
+
+// Sobol point of the dimension, following Burley, "Practical Hash-based Owen
+// Scrambling". Every run of SOBOL_DIMENSIONS dimensions reuses the first Sobol
+// dimensions with its own nested uniform scrambling and its own shuffled order of
+// the points, so that runs and seeds see different points
+fn scrambled_sobol(index: u32, dimension: u32, seed: u64) -> f32 {
+    let run = dimension as usize / SOBOL_DIMENSIONS;
+    let run_seed = hash(seed, run as u64);
+    let index = nested_uniform_scramble(index, run_seed);
+
+    let component = dimension as usize % SOBOL_DIMENSIONS;
+    let mut bits = 0;
+    let mut remaining = index;
+    let mut bit = 0;
+    while remaining != 0 {
+        if remaining & 1 != 0 {
+            bits ^= SOBOL_MATRICES[component][bit];
+        }
+        remaining >>= 1;
+        bit += 1;
+    }
+
+    let component_seed = hash(run_seed as u64, component as u64);
+    unit_float(nested_uniform_scramble(bits, component_seed))
+}
+
+// Owen scrambling of the bits of a fixed point number, which randomizes the point
+// while keeping the stratification of the sequence
+fn nested_uniform_scramble(bits: u32, seed: u32) -> u32 {
+    laine_karras_permutation(bits.reverse_bits(), seed).reverse_bits()
+}
+
+// Hash where every bit only depends on the bits below it, so that reversed it
+// only depends on the more significant bits as Owen scrambling requires
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6C50_B47C);
+    x ^= x.wrapping_mul(0xB82F_1E52);
+    x ^= x.wrapping_mul(0xC7AF_E638);
+    x ^= x.wrapping_mul(0x8D22_F6E6);
+    x
+}
+
+// Direction numbers from the primitive polynomials of Joe and Kuo. The first
+// dimension is the van der Corput sequence, and every other one is given by the
+// degree of its polynomial, the inner coefficients and the initial values of m
+const fn sobol_matrices() -> [[u32; 32]; SOBOL_DIMENSIONS] {
+    let polynomials: [(usize, u32, [u32; 3]); SOBOL_DIMENSIONS - 1] =
+        [(1, 0, [1, 0, 0]), (2, 1, [1, 3, 0]), (3, 1, [1, 3, 1])];
+
+    let mut matrices = [[0; 32]; SOBOL_DIMENSIONS];
+    let mut bit = 0;
+    while bit < 32 {
+        matrices[0][bit] = 1 << (31 - bit);
+        bit += 1;
+    }
+
+    let mut dimension = 1;
+    while dimension < SOBOL_DIMENSIONS {
+        let (degree, coefficients, initial) = polynomials[dimension - 1];
+        let mut m = [0u32; 32];
+        let mut k = 0;
+        while k < 32 {
+            m[k] = if k < degree {
+                initial[k]
+            } else {
+                let mut value = m[k - degree] ^ (m[k - degree] << degree);
+                let mut j = 1;
+                while j < degree {
+                    if (coefficients >> (degree - 1 - j)) & 1 != 0 {
+                        value ^= m[k - j] << j;
+                    }
+                    j += 1;
+                }
+                value
+            };
+            matrices[dimension][k] = m[k] << (31 - k);
+            k += 1;
+        }
+        dimension += 1;
+    }
+
+    matrices
+}
+
+// Value of the blue noise mask for the pixel, looked up at an offset that differs
+// between dimensions so that they are not dithered alike
+fn blue_noise(x: u32, y: u32, dimension: u32) -> f32 {
+    let offset = hash(BLUE_NOISE_SEED, dimension as u64);
+    let size = BLUE_NOISE_SIZE as u32;
+    let mask_x = (x + (offset & 0xFFFF)) % size;
+    let mask_y = (y + (offset >> 16)) % size;
+    blue_noise_mask()[(mask_y * size + mask_x) as usize]
+}
+
+fn blue_noise_mask() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+// Tileable blue noise mask built with Ulichney's void-and-cluster method. Every pixel
+// gets a rank, assigned by taking points out of the tightest clusters of a well
+// spread initial pattern and by filling the largest voids. Clusters and voids are
+// found from an energy summing a Gaussian of the distance to every set pixel
+fn void_and_cluster() -> Vec<f32> {
+    let size = BLUE_NOISE_SIZE;
+    let pixels = size * size;
+
+    // Gaussian of the wrapped distance between two pixels, by offset
+    let kernel: Vec<f32> = (0..pixels)
+        .map(|offset| {
+            let wrap = |d: usize| d.min(size - d) as f32;
+            let (dx, dy) = (wrap(offset % size), wrap(offset / size));
+            (-(dx * dx + dy * dy) / (2. * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp()
+        })
+        .collect();
+
+    let toggle = |pattern: &mut [bool], energy: &mut [f32], pixel: usize| {
+        pattern[pixel] = !pattern[pixel];
+        let sign = if pattern[pixel] { 1. } else { -1. };
+        let (px, py) = (pixel % size, pixel / size);
+        for (other, value) in energy.iter_mut().enumerate() {
+            let dx = (other % size + size - px) % size;
+            let dy = (other / size + size - py) % size;
+            *value += sign * kernel[dy * size + dx];
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f32]| {
+        (0..pixels)
+            .filter(|&pixel| pattern[pixel])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap_or(0)
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| {
+        (0..pixels)
+            .filter(|&pixel| !pattern[pixel])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap_or(0)
+    };
+
+    // Random initial points, spread out by moving the point in the tightest cluster
+    // to the largest void until it would end up where it came from. Ties between
+    // equal energies could keep a point moving back and forth, so there are never
+    // more moves than pixels
+    let mut rng = seeded_rng(BLUE_NOISE_SEED);
+    let initial_points = pixels / 10;
+    let mut pattern = vec![false; pixels];
+    let mut energy = vec![0.; pixels];
+    let mut placed = 0;
+    while placed < initial_points {
+        let pixel = ((random_num(&mut rng) * pixels as f32) as usize).min(pixels - 1);
+        if !pattern[pixel] {
+            toggle(&mut pattern, &mut energy, pixel);
+            placed += 1;
+        }
+    }
+    for _ in 0..pixels {
+        let cluster = tightest_cluster(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, cluster);
+        let void = largest_void(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; pixels];
+
+    // Initial points are ranked below the rest, the most clustered ones last
+    let (mut removing, mut removing_energy) = (pattern.clone(), energy.clone());
+    for rank in (0..initial_points).rev() {
+        let cluster = tightest_cluster(&removing, &removing_energy);
+        toggle(&mut removing, &mut removing_energy, cluster);
+        ranks[cluster] = rank;
+    }
+
+    // The largest void of the set pixels is also the tightest cluster of the unset
+    // ones, so filling voids ranks the rest of the pixels from both ends
+    for rank in initial_points..pixels {
+        let void = largest_void(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, void);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / pixels as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::sample_rng;
+
+    const SAMPLERS: [Sampler; 5] = [
+        Sampler::Independent,
+        Sampler::Stratified,
+        Sampler::Halton,
+        Sampler::Sobol,
+        Sampler::BlueNoise,
+    ];
+
+    // Values of the first dimensions of one sample, drawn the way the camera does,
+    // going past the dimensions Halton and Sobol cover on their own
+    fn path(sampler: Sampler, seed: u64, x: u32, y: u32, sample: u32) -> Vec<f32> {
+        let mut rng = sample_rng(seed, x, y, sample);
+        let mut samples = sampler.start(seed, x, y, sample, 16);
+        let mut values = vec![samples.next_1d(&mut rng)];
+        while values.len() < 80 {
+            let (u, v) = samples.next_2d(&mut rng);
+            values.extend([u, v, samples.next_1d(&mut rng)]);
+        }
+        values
+    }
+
+    // First two dimensions of every sample of a pixel taking `count` samples
+    fn points(sampler: Sampler, count: u32) -> Vec<(f32, f32)> {
+        let mut rng = seeded_rng(0);
+        (0..count)
+            .map(|sample| sampler.start(9, 3, 5, sample, count).next_2d(&mut rng))
+            .collect()
+    }
+
+    fn assert_one_point_per_cell(points: &[(f32, f32)], columns: u32, rows: u32) {
+        let mut cells = vec![0; (columns * rows) as usize];
+        for &(x, y) in points {
+            let column = (x * columns as f32) as u32;
+            let row = (y * rows as f32) as u32;
+            cells[(row * columns + column) as usize] += 1;
+        }
+        assert!(
+            cells.iter().all(|&count| count == 1),
+            "{columns}x{rows} cells hold {cells:?}"
+        );
+    }
+
+    #[test]
+    fn values_lie_in_the_unit_interval() {
+        for sampler in SAMPLERS {
+            for sample in 0..40 {
+                for value in path(sampler, 1, sample % 7, sample % 5, sample) {
+                    assert!((0. ..1.).contains(&value), "{sampler:?} gave {value}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn values_depend_only_on_the_seed_pixel_and_sample() {
+        for sampler in SAMPLERS {
+            assert_eq!(path(sampler, 3, 10, 20, 4), path(sampler, 3, 10, 20, 4));
+            assert_ne!(path(sampler, 3, 10, 20, 4), path(sampler, 3, 10, 20, 5));
+            assert_ne!(path(sampler, 3, 10, 20, 4), path(sampler, 3, 11, 20, 4));
+            assert_ne!(path(sampler, 3, 10, 20, 4), path(sampler, 4, 10, 20, 4));
+        }
+    }
+
+    #[test]
+    fn sobol_points_fill_every_elementary_interval() {
+        // Any power of two of points splits into one point per cell of every grid of
+        // that many cells whose sides are powers of two
+        for sampler in [Sampler::Sobol, Sampler::BlueNoise] {
+            for log_count in [4, 6] {
+                let points = points(sampler, 1 << log_count);
+                let points = match sampler {
+                    // The mask shifts all points of a pixel alike, modulo one
+                    Sampler::BlueNoise => {
+                        let shift = (blue_noise(3, 5, 0), blue_noise(3, 5, 1));
+                        points
+                            .iter()
+                            .map(|&(x, y)| ((x - shift.0 + 1.).fract(), (y - shift.1 + 1.).fract()))
+                            .collect()
+                    }
+                    _ => points,
+                };
+                for log_columns in 0..=log_count {
+                    let log_rows = log_count - log_columns;
+                    assert_one_point_per_cell(&points, 1 << log_columns, 1 << log_rows);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_points_fill_every_stratum() {
+        // Every sample lands in its own cell of the roughly square grid, and in its own
+        // row and column of the finer grids splitting the square one way only
+        for (count, columns) in [(9, 3), (16, 4), (12, 3)] {
+            let points = points(Sampler::Stratified, count);
+            assert_one_point_per_cell(&points, columns, count / columns);
+            assert_one_point_per_cell(&points, count, 1);
+            assert_one_point_per_cell(&points, 1, count);
+        }
+    }
+}
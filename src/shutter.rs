@@ -0,0 +1,145 @@
+// How the shutter opens and closes over the exposure, which weights the ray times
+// and so the shape of motion blur streaks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShutterCurve {
+    // Fully open for the whole interval, spreading times uniformly
+    Box,
+    // Opening linearly until the middle of the interval and closing after, which
+    // fades the ends of motion blur streaks
+    Triangle,
+    // Scanlines exposed one after the other from the top of the image, each for the
+    // given fraction of the interval. Fast motion leans over the image as with the
+    // electronic shutter of a phone camera
+    Rolling { exposure: f32 },
+}
+
+// Interval of ray times over which the camera exposes the image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shutter {
+    pub open: f32,
+    pub close: f32,
+    pub curve: ShutterCurve,
+}
+
+impl Default for Shutter {
+    fn default() -> Self {
+        Self::new(0., 1.)
+    }
+}
+
+impl Shutter {
+    pub fn new(open: f32, close: f32) -> Self {
+        Self {
+            open,
+            close,
+            curve: ShutterCurve::Box,
+        }
+    }
+
+    // Same shutter moved later in time, for instance to the start of an animation frame
+    pub fn shifted(self, offset: f32) -> Self {
+        Self {
+            open: self.open + offset,
+            close: self.close + offset,
+            ..self
+        }
+    }
+
+    // Maps a uniform sample in [0,1) to a ray time for the given row of an image with
+    // the given height
+    pub fn sample(&self, u: f32, row: u32, image_height: u32) -> f32 {
+        let fraction = match self.curve {
+            ShutterCurve::Box => u,
+            // Inverse of the cumulative distribution of the triangle
+            ShutterCurve::Triangle => {
+                if u < 0.5 {
+                    (0.5 * u).sqrt()
+                } else {
+                    1. - (0.5 * (1. - u)).sqrt()
+                }
+            }
+            ShutterCurve::Rolling { exposure } => {
+                let exposure = exposure.clamp(0., 1.);
+                let progress = row as f32 / (image_height.max(2) - 1) as f32;
+                (1. - exposure) * progress + exposure * u
+            }
+        };
+
+        self.open + fraction * (self.close - self.open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(shutter: Shutter, row: u32) -> Vec<f32> {
+        (0..1000)
+            .map(|i| shutter.sample((i as f32 + 0.5) / 1000., row, 100))
+            .collect()
+    }
+
+    #[test]
+    fn times_stay_within_the_interval() {
+        for curve in [
+            ShutterCurve::Box,
+            ShutterCurve::Triangle,
+            ShutterCurve::Rolling { exposure: 0.25 },
+        ] {
+            let shutter = Shutter {
+                curve,
+                ..Shutter::new(2., 3.)
+            };
+            for row in [0, 50, 99] {
+                for time in times(shutter, row) {
+                    assert!((2. ..=3.).contains(&time), "{curve:?} gave {time}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn triangle_concentrates_times_in_the_middle() {
+        let shutter = Shutter {
+            curve: ShutterCurve::Triangle,
+            ..Shutter::default()
+        };
+        let middle = times(shutter, 0)
+            .iter()
+            .filter(|&&time| (0.25..0.75).contains(&time))
+            .count();
+
+        // Three quarters of the area of the triangle lie in its middle half
+        assert!((middle as f32 / 1000. - 0.75).abs() < 0.01, "{middle}");
+        assert_eq!(shutter.sample(0.5, 0, 1), 0.5);
+    }
+
+    #[test]
+    fn rolling_shutter_exposes_rows_in_turn() {
+        let shutter = Shutter {
+            curve: ShutterCurve::Rolling { exposure: 0.2 },
+            ..Shutter::default()
+        };
+        let span = |row| {
+            let times = times(shutter, row);
+            (times[0], times[times.len() - 1])
+        };
+
+        let (top_start, top_end) = span(0);
+        let (bottom_start, bottom_end) = span(99);
+        assert!(top_start < 0.01 && (top_end - 0.2).abs() < 0.01);
+        assert!((bottom_start - 0.8).abs() < 0.01 && bottom_end > 0.99);
+    }
+
+    #[test]
+    fn shifting_moves_the_whole_interval() {
+        let shutter = Shutter {
+            curve: ShutterCurve::Triangle,
+            ..Shutter::new(0.5, 1.)
+        }
+        .shifted(10.);
+
+        assert_eq!((shutter.open, shutter.close), (10.5, 11.));
+        assert_eq!(shutter.curve, ShutterCurve::Triangle);
+    }
+}
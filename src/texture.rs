@@ -1,4 +1,5 @@
 use std::path::Path;
+use std::sync::Arc;
 
 use image::{ImageError, ImageReader, RgbImage};
 
@@ -81,9 +82,10 @@ impl CheckerTexture {
     }
 }
 
+// The decoded image is shared between clones of the texture
 #[derive(Clone)]
 pub struct ImageTexture {
-    image: RgbImage,
+    image: Arc<RgbImage>,
 }
     
 impl ImageTexture {
@@ -91,21 +93,21 @@ impl ImageTexture {
         let image = ImageReader::open(Path::new(&file))?.decode()?.into_rgb8();
         
         Ok(Self {
-            image
+            image: Arc::new(image)
         })
 
     }
 
     pub fn value(&self, mut u: f32, mut v: f32, _point: Vec3) -> Vec3 {
-        if self.image.height() <= 0 {
+        if self.image.height() == 0 {
             return Vec3::new(0., 1., 1.)
         }
 
         u = Interval::new(0.,1.).clamp(u);
         v = 1.0 - Interval::new(0.,1.).clamp(v);
 
-        let i = (u as u32) * (self.image.width() - 1);
-        let j = (v as u32)  * (self.image.height() - 1);
+        let i = (u * (self.image.width() - 1) as f32) as u32;
+        let j = (v * (self.image.height() - 1) as f32) as u32;
         let pixel = self.image.get_pixel(i,j);
 
         let color_scale = 1. / 255.;
@@ -116,14 +118,21 @@ impl ImageTexture {
 
 #[derive(Clone)]
 pub struct PerlinTexture {
-    noise: Perlin,
+    noise: Box<Perlin>,
     scale: f32,
 }
 
 impl PerlinTexture {
     pub fn new(scale: f32) -> PerlinTexture {
         Self {
-            noise: Perlin::new(),
+            noise: Box::new(Perlin::new()),
+            scale
+        }
+    }
+
+    pub fn new_from_seed(scale: f32, seed: u64) -> PerlinTexture {
+        Self {
+            noise: Box::new(Perlin::new_from_seed(seed)),
             scale
         }
     }
@@ -0,0 +1,238 @@
+use crate::interval::Interval;
+use crate::vec3::Vec3;
+
+// Operators compressing unbounded linear radiance into the displayable [0,1] range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapOperator {
+    Clamp,                                 // Cuts off everything above one
+    Reinhard,                              // c / (1 + c)
+    ExtendedReinhard { white_point: f32 }, // Reinhard reaching one exactly at the white point
+    Aces,                                  // Narkowicz's fit of the ACES filmic curve
+    Hable,                                 // Uncharted 2 filmic curve
+}
+
+impl ToneMapOperator {
+    // Extended Reinhard operator mapping the given radiance to one, which has to be
+    // positive
+    pub fn new_extended_reinhard(white_point: f32) -> Self {
+        let operator = Self::ExtendedReinhard { white_point };
+        operator.validate();
+        operator
+    }
+
+    // Whether the operator can map colors with its parameters, which only fails for
+    // extended Reinhard white points that are not positive
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Self::ExtendedReinhard { white_point } => white_point > 0.,
+            _ => true,
+        }
+    }
+
+    // Panics on parameters the operator cannot map colors with, which would otherwise
+    // turn every pixel into NaN
+    pub(crate) fn validate(&self) {
+        assert!(
+            self.is_valid(),
+            "extended Reinhard white point must be positive"
+        );
+    }
+
+    pub fn map(&self, color: Vec3) -> Vec3 {
+        // The variants can be built directly, so invalid white points fall back to
+        // plain Reinhard, the limit of an ever brighter white point
+        let operator = if self.is_valid() {
+            *self
+        } else {
+            Self::Reinhard
+        };
+
+        // Curves like extended Reinhard and Hable overshoot one past their white
+        // point, so every result is clamped into the displayable range
+        let map_channel = |c: f32| -> f32 {
+            let c = c.max(0.);
+            let mapped = match operator {
+                Self::Clamp => c,
+                Self::Reinhard => c / (1. + c),
+                Self::ExtendedReinhard { white_point } => {
+                    c * (1. + c / (white_point * white_point)) / (1. + c)
+                }
+                Self::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+                Self::Hable => {
+                    let exposure_bias = 2.;
+                    let white_point = 11.2;
+                    Self::hable_partial(exposure_bias * c) / Self::hable_partial(white_point)
+                }
+            };
+            Interval::UNIT.clamp(mapped)
+        };
+
+        Vec3::new(
+            map_channel(color.x),
+            map_channel(color.y),
+            map_channel(color.z),
+        )
+    }
+
+    fn hable_partial(x: f32) -> f32 {
+        let (a, b, c, d, e, f) = (0.15, 0.5, 0.1, 0.2, 0.02, 0.3);
+        ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+    }
+}
+
+// Curves encoding display linear values for storage in 8-bit images
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferCurve {
+    Linear,
+    Gamma2, // Square root approximation of a display gamma
+    Srgb,   // Piecewise sRGB curve with its linear toe
+}
+
+impl TransferCurve {
+    pub fn encode(&self, linear_component: f32) -> f32 {
+        let linear_component = linear_component.max(0.);
+        match self {
+            Self::Linear => linear_component,
+            Self::Gamma2 => linear_component.sqrt(),
+            Self::Srgb => {
+                if linear_component <= 0.003_130_8 {
+                    12.92 * linear_component
+                } else {
+                    1.055 * linear_component.powf(1. / 2.4) - 0.055
+                }
+            }
+        }
+    }
+}
+
+// Stage converting the linear HDR framebuffer into 8-bit colors: the exposure
+// scales radiance, the operator compresses it and the transfer curve encodes it.
+// The default reproduces the original clamp and square root gamma
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    pub exposure: f32, // In EV stops, each one doubling the brightness
+    pub transfer: TransferCurve,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            operator: ToneMapOperator::Clamp,
+            exposure: 0.,
+            transfer: TransferCurve::Gamma2,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator, exposure: f32, transfer: TransferCurve) -> Self {
+        operator.validate();
+        Self {
+            operator,
+            exposure,
+            transfer,
+        }
+    }
+
+    // Maps a linear color to display encoded values in [0,1]
+    pub fn apply(&self, color: Vec3) -> Vec3 {
+        let exposed = 2_f32.powf(self.exposure) * color;
+        let mapped = self.operator.map(exposed);
+
+        Vec3::new(
+            self.transfer.encode(mapped.x),
+            self.transfer.encode(mapped.y),
+            self.transfer.encode(mapped.z),
+        )
+    }
+
+    pub fn to_bytes(&self, color: Vec3) -> [u8; 3] {
+        let encoded = self.apply(color);
+
+        // Transform [0,1] values into byte [0,255] range
+        let intensity = Interval::new(0., 0.999);
+        [
+            (256. * intensity.clamp(encoded.x)) as u8,
+            (256. * intensity.clamp(encoded.y)) as u8,
+            (256. * intensity.clamp(encoded.z)) as u8,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(color: Vec3) -> [f32; 3] {
+        [color.x, color.y, color.z]
+    }
+
+    #[test]
+    fn operators_stay_in_the_unit_range() {
+        let operators = [
+            ToneMapOperator::Clamp,
+            ToneMapOperator::Reinhard,
+            ToneMapOperator::new_extended_reinhard(4.),
+            ToneMapOperator::Aces,
+            ToneMapOperator::Hable,
+        ];
+        for operator in operators {
+            for c in [-1., 0., 0.18, 1., 16., 1e6] {
+                let mapped = operator.map(Vec3::new(c, c, c));
+                assert!(
+                    (0. ..=1.).contains(&mapped.x),
+                    "{operator:?} maps {c} to {mapped:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_point_to_one() {
+        let mapped = ToneMapOperator::new_extended_reinhard(4.).map(Vec3::new(4., 1., 0.));
+
+        assert!((mapped.x - 1.).abs() < 1e-6);
+        assert!(mapped.y > 0. && mapped.y < 1.);
+        assert_eq!(mapped.z, 0.);
+    }
+
+    #[test]
+    #[should_panic(expected = "white point must be positive")]
+    fn extended_reinhard_rejects_zero_white_point() {
+        ToneMapOperator::new_extended_reinhard(0.);
+    }
+
+    #[test]
+    #[should_panic(expected = "white point must be positive")]
+    fn extended_reinhard_rejects_nan_white_point() {
+        ToneMapOperator::new_extended_reinhard(f32::NAN);
+    }
+
+    #[test]
+    fn invalid_white_point_built_directly_falls_back_to_reinhard() {
+        let color = Vec3::new(0.5, 2., 8.);
+        let reinhard = channels(ToneMapOperator::Reinhard.map(color));
+        for white_point in [0., -1., f32::NAN] {
+            let mapped = ToneMapOperator::ExtendedReinhard { white_point }.map(color);
+            assert_eq!(channels(mapped), reinhard);
+        }
+    }
+
+    #[test]
+    fn exposure_doubles_per_stop() {
+        let linear = ToneMapping::new(ToneMapOperator::Clamp, 1., TransferCurve::Linear);
+        assert_eq!(
+            channels(linear.apply(Vec3::new(0.25, 0.125, 1.))),
+            [0.5, 0.25, 1.]
+        );
+    }
+
+    #[test]
+    fn srgb_curve_is_continuous_at_its_toe() {
+        let below = TransferCurve::Srgb.encode(0.003_130_8);
+        let above = TransferCurve::Srgb.encode(0.003_130_9);
+        assert!((above - below).abs() < 1e-5);
+        assert!((TransferCurve::Srgb.encode(1.) - 1.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitData, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    utilities::RenderRng,
+    vec3::Vec3,
+};
+
+pub struct Triangle {
+    vertices: [Vec3; 3],
+    first_edge: Vec3,
+    second_edge: Vec3,
+    normal: Vec3,
+    vertex_normals: Option<[Vec3; 3]>,
+    uvs: [(f32, f32); 3],
+    material: Material,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, material: Material) -> Triangle {
+        let first_edge = b - a;
+        let second_edge = c - a;
+
+        // Geometric normal following the counter-clockwise winding of the vertices
+        let normal = Vec3::cross(first_edge, second_edge).unit();
+
+        // The union of two boxes sharing a vertex spans all three vertices
+        let bbox = Aabb::new_from_boxes(Aabb::new_from_points(a, b), Aabb::new_from_points(a, c));
+
+        Self {
+            vertices: [a, b, c],
+            first_edge,
+            second_edge,
+            normal,
+            vertex_normals: None,
+            uvs: [(0., 0.), (1., 0.), (0., 1.)],
+            material,
+            bbox,
+        }
+    }
+
+    // Per-vertex normals interpolated across the face for smooth shading
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.vertex_normals = Some(normals.map(|normal| normal.unit()));
+        self
+    }
+
+    // Texture coordinates of each vertex, by default the corners (0,0), (1,0), (0,1)
+    pub fn with_uvs(mut self, uvs: [(f32, f32); 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+}
+
+// Moller-Trumbore intersection of a ray with the triangle spanned by two edges from
+// its first vertex. Solves for the ray parameter and the barycentric weights of the
+// second and third vertices at once, returning them in that order
+pub(crate) fn intersect_triangle(
+    first_vertex: Vec3,
+    first_edge: Vec3,
+    second_edge: Vec3,
+    ray: Ray,
+    interval: Interval,
+) -> Option<(f32, f32, f32)> {
+    let p = Vec3::cross(ray.direction, second_edge);
+    let determinant = Vec3::dot(first_edge, p);
+
+    // Rule out ray parallel to the triangle plane -> no intersection. The determinant
+    // grows with the lengths of the edges and the ray direction, so it is compared
+    // against their product to treat tiny and huge triangles alike
+    let scale = (first_edge.length_squared()
+        * second_edge.length_squared()
+        * ray.direction.length_squared())
+    .sqrt();
+    if determinant.abs() <= 1e-7 * scale {
+        return None;
+    }
+    let inverse_determinant = 1. / determinant;
+
+    let origin_offset = ray.origin - first_vertex;
+    let beta = inverse_determinant * Vec3::dot(origin_offset, p);
+    if !Interval::UNIT.contains(beta) {
+        return None;
+    }
+
+    let q = Vec3::cross(origin_offset, first_edge);
+    let gamma = inverse_determinant * Vec3::dot(ray.direction, q);
+    if gamma < 0. || beta + gamma > 1. {
+        return None;
+    }
+
+    let t_intersection = inverse_determinant * Vec3::dot(second_edge, q);
+    if !interval.contains(t_intersection) {
+        return None;
+    }
+
+    Some((t_intersection, beta, gamma))
+}
+
+// Uniform point on the triangle spanned by two edges from its first vertex. Points of
+// the parallelogram falling outside the triangle are folded back into it
+pub(crate) fn sample_triangle(
+    first_vertex: Vec3,
+    first_edge: Vec3,
+    second_edge: Vec3,
+    sample: (f32, f32),
+) -> Vec3 {
+    let (mut beta, mut gamma) = sample;
+    if beta + gamma > 1. {
+        beta = 1. - beta;
+        gamma = 1. - gamma;
+    }
+    first_vertex + beta * first_edge + gamma * second_edge
+}
+
+// Density per solid angle of the direction of the ray, hitting the triangle at the
+// given distance along it, when points are drawn uniformly over the triangle
+pub(crate) fn triangle_pdf(first_edge: Vec3, second_edge: Vec3, ray: Ray, t: f32) -> f32 {
+    let normal = Vec3::cross(first_edge, second_edge);
+    let area = 0.5 * normal.length();
+    let distance_squared = t * t * ray.direction.length_squared();
+    let cosine =
+        (Vec3::dot(ray.direction, normal) / (ray.direction.length() * normal.length())).abs();
+    distance_squared / (cosine * area)
+}
+
+impl Hittable for Triangle {
+    fn hit(
+        &self,
+        ray: Ray,
+        interval: Interval,
+        hit_data: &mut HitData,
+        _rng: &mut RenderRng,
+    ) -> bool {
+        let Some((t_intersection, beta, gamma)) = intersect_triangle(
+            self.vertices[0],
+            self.first_edge,
+            self.second_edge,
+            ray,
+            interval,
+        ) else {
+            return false;
+        };
+
+        let alpha = 1. - beta - gamma;
+        hit_data.hit_along_ray = t_intersection;
+        hit_data.point = ray.at(t_intersection);
+        hit_data.material = Some(self.material.clone());
+        hit_data.set_face_normal(ray, self.normal);
+
+        if let Some([normal_a, normal_b, normal_c]) = self.vertex_normals {
+            // Shading normal oriented to the same side of the surface as the ray
+            let shading_normal = (alpha * normal_a + beta * normal_b + gamma * normal_c).unit();
+            hit_data.normal = if hit_data.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+
+        let [(u_a, v_a), (u_b, v_b), (u_c, v_c)] = self.uvs;
+        hit_data.u = alpha * u_a + beta * u_b + gamma * u_c;
+        hit_data.v = alpha * v_a + beta * v_b + gamma * v_c;
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, ray: Ray, _rng: &mut RenderRng) -> f32 {
+        let interval = Interval::new(0.001, f32::INFINITY);
+        match intersect_triangle(
+            self.vertices[0],
+            self.first_edge,
+            self.second_edge,
+            ray,
+            interval,
+        ) {
+            Some((t, _, _)) => triangle_pdf(self.first_edge, self.second_edge, ray, t),
+            None => 0.,
+        }
+    }
+
+    fn random(&self, origin: Vec3, _time: f32, sample: (f32, f32)) -> Vec3 {
+        sample_triangle(self.vertices[0], self.first_edge, self.second_edge, sample) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::assert_sampling_is_consistent;
+    use crate::material::Lambertian;
+    use crate::utilities::seeded_rng;
+    use std::f32::consts::PI;
+
+    fn triangle(size: f32, distance: f32) -> Triangle {
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::new(0.5, 0.5, 0.5)));
+        Triangle::new(
+            Vec3::new(0., 0., -distance),
+            Vec3::new(size, 0., -distance),
+            Vec3::new(0., size, -distance),
+            material,
+        )
+    }
+
+    fn hit(triangle: &Triangle, ray: Ray) -> Option<HitData> {
+        let mut hit_data = HitData::default();
+        let interval = Interval::new(0.001, f32::INFINITY);
+        triangle
+            .hit(ray, interval, &mut hit_data, &mut seeded_rng(0))
+            .then_some(hit_data)
+    }
+
+    #[test]
+    fn tiny_triangle_is_hit() {
+        let triangle = triangle(1e-4, 1.);
+        let ray = Ray::new(Vec3::new(2.5e-5, 2.5e-5, 0.), Vec3::new(0., 0., -1.), 0.);
+
+        let hit_data = hit(&triangle, ray).expect("ray should hit the triangle");
+        assert!((hit_data.hit_along_ray - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn small_triangle_is_hit_by_short_direction() {
+        let triangle = triangle(1e-3, 10.);
+        let ray = Ray::new(Vec3::new(2.5e-4, 2.5e-4, 0.), Vec3::new(0., 0., -1e-3), 0.);
+
+        let hit_data = hit(&triangle, ray).expect("ray should hit the triangle");
+        assert!((hit_data.hit_along_ray - 1e4).abs() < 1.);
+    }
+
+    #[test]
+    fn ray_parallel_to_triangle_misses() {
+        let triangle = triangle(1., 1.);
+        let ray = Ray::new(Vec3::new(-1., 0.25, -1.), Vec3::new(1., 0., 0.), 0.);
+
+        assert!(hit(&triangle, ray).is_none());
+    }
+
+    #[test]
+    fn hit_interpolates_uvs_and_normals() {
+        let up = Vec3::new(0., 1., 0.);
+        let triangle = triangle(1., 1.)
+            .with_uvs([(0., 0.), (1., 0.), (1., 1.)])
+            .with_normals([Vec3::new(0., 0., 1.), up, up]);
+        let ray = Ray::new(Vec3::new(0.5, 0.25, 0.), Vec3::new(0., 0., -1.), 0.);
+
+        let hit_data = hit(&triangle, ray).expect("ray should hit the triangle");
+        // Barycentric weights 0.25, 0.5 and 0.25 of the three vertices
+        assert!((hit_data.u - 0.75).abs() < 1e-6);
+        assert!((hit_data.v - 0.25).abs() < 1e-6);
+        assert!(hit_data.front_face);
+        let expected = Vec3::new(0., 0.75, 0.25).unit();
+        assert!((hit_data.normal - expected).length() < 1e-5);
+    }
+
+    #[test]
+    fn sampling_matches_the_density() {
+        // Half of the unit square above the origin's foot, cut along the diagonal that
+        // splits it into two mirror images
+        let material = Material::Lambertian(Lambertian::new_from_color(Vec3::splat(0.5)));
+        let triangle = Triangle::new(
+            Vec3::new(0., 0., -1.),
+            Vec3::new(1., 0., -1.),
+            Vec3::new(1., 1., -1.),
+            material,
+        );
+        assert_sampling_is_consistent(&triangle, Vec3::ZERO, PI / 12.);
+    }
+}
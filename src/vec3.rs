@@ -1,4 +1,5 @@
 use std::{
+    f32::consts::PI,
     iter::Sum,
     ops::{Add, AddAssign, Mul, Neg, Sub},
 };
@@ -50,7 +51,7 @@ impl Vec3 {
         z: 0.,
     };
 
-    pub fn new(x: f32, y: f32, z: f32) -> Self {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
         Self { x, y, z }
     }
 
@@ -79,9 +80,9 @@ impl Vec3 {
     }
 
     pub fn minus(&mut self) {
-        self.x *= -1 as f32;
-        self.y *= -1 as f32;
-        self.z *= -1 as f32;
+        self.x *= -1.;
+        self.y *= -1.;
+        self.z *= -1.;
     }
 
     pub fn add(&mut self, other: Self) {
@@ -120,45 +121,97 @@ impl Vec3 {
         }
     }
 
-    pub fn random() -> Self {
-        Self::new(random_num(), random_num(), random_num())
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::new(random_num(rng), random_num(rng), random_num(rng))
     }
 
-    pub fn random_in_interval(min: f32, max: f32) -> Self {
+    pub fn random_in_interval<R: Rng + ?Sized>(min: f32, max: f32, rng: &mut R) -> Self {
         Self::new(
-            random_in_interval(min, max),
-            random_in_interval(min, max),
-            random_in_interval(min, max),
+            random_in_interval(min, max, rng),
+            random_in_interval(min, max, rng),
+            random_in_interval(min, max, rng),
         )
     }
 
-    pub fn random_unit_vector() -> Self {
+    pub fn random_unit_vector<R: Rng + ?Sized>(rng: &mut R) -> Self {
         loop {
-            let sample = Self::random_in_interval(-1., 1.);
+            let sample = Self::random_in_interval(-1., 1., rng);
             if sample.length_squared() < 1. {
                 return sample.unit();
             }
         }
     }
 
-    pub fn random_on_hemisphere(normal: Self) -> Self {
-        let sample = Self::random_unit_vector();
+    pub fn random_on_hemisphere<R: Rng + ?Sized>(normal: Self, rng: &mut R) -> Self {
+        let sample = Self::random_unit_vector(rng);
         if Self::dot(sample, normal) > 0. {
-            return sample;
+            sample
         } else {
-            return -1. * sample;
+            -sample
         }
     }
 
-    pub fn random_in_unit_disc() -> Self {
+    pub fn random_in_unit_disc<R: Rng + ?Sized>(rng: &mut R) -> Self {
         loop {
-            let sample = Vec3::new(random_in_interval(-1., 1.), random_in_interval(-1., 1.), 0.);
+            let sample = Vec3::new(
+                random_in_interval(-1., 1., rng),
+                random_in_interval(-1., 1., rng),
+                0.,
+            );
             if sample.length_squared() < 1. {
                 return sample;
             }
         }
     }
 
+    // The samplers below map a point of the unit square, drawn at random or from a
+    // stratified or low-discrepancy sequence, to directions about the z axis or
+    // points of the unit disc. Pair them with an Onb to orient them along a normal
+
+    // Direction above the xy plane with density cos(theta) / pi, found by lifting a
+    // uniform point of the disc onto the hemisphere
+    pub fn cosine_hemisphere(sample: (f32, f32)) -> Self {
+        let disc = Self::concentric_disc(sample);
+        let z = (1. - disc.x * disc.x - disc.y * disc.y).max(0.).sqrt();
+        Self::new(disc.x, disc.y, z)
+    }
+
+    // Direction above the xy plane with density 1 / (2 pi)
+    pub fn uniform_hemisphere(sample: (f32, f32)) -> Self {
+        Self::uniform_cone(sample, 0.)
+    }
+
+    // Direction with density 1 / (4 pi)
+    pub fn uniform_sphere(sample: (f32, f32)) -> Self {
+        Self::uniform_cone(sample, -1.)
+    }
+
+    // Direction within the cone about the z axis whose half angle has the given
+    // cosine, with density 1 / (2 pi (1 - cos_theta_max))
+    pub fn uniform_cone(sample: (f32, f32), cos_theta_max: f32) -> Self {
+        let z = 1. - sample.0 * (1. - cos_theta_max);
+        let sin_theta = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * PI * sample.1;
+        Self::new(sin_theta * phi.cos(), sin_theta * phi.sin(), z)
+    }
+
+    // Point of the unit disc in the xy plane. Shirley's concentric mapping keeps
+    // neighbouring samples of the square close together, so stratification survives
+    pub fn concentric_disc(sample: (f32, f32)) -> Self {
+        let a = 2. * sample.0 - 1.;
+        let b = 2. * sample.1 - 1.;
+        if a == 0. && b == 0. {
+            return Self::ZERO;
+        }
+
+        let (radius, theta) = if a.abs() > b.abs() {
+            (a, (PI / 4.) * (b / a))
+        } else {
+            (b, PI / 2. - (PI / 4.) * (a / b))
+        };
+        Self::new(radius * theta.cos(), radius * theta.sin(), 0.)
+    }
+
     // Returns true in vec is close enough in magnitude to zero
     pub fn near_zero(&self) -> bool {
         let tolerance = 10.0_f32.powf(-8.);
@@ -175,7 +228,7 @@ impl Vec3 {
     pub fn refract(incoming: Vec3, normal: Vec3, ref_ratio: f32) -> Vec3 {
         let cos_theta = (Self::dot(-1. * incoming, normal)).min(1.);
         let ref_vec_perp = ref_ratio * (incoming + cos_theta * normal);
-        let ref_vec_par = (-1. * (1. - ref_vec_perp.length_squared()).sqrt()) * normal;
+        let ref_vec_par = -(1. - ref_vec_perp.length_squared()).sqrt() * normal;
         ref_vec_perp + ref_vec_par
     }
 
@@ -243,3 +296,73 @@ impl Sum for Vec3 {
         iter.fold(Vec3::ZERO, |total, vec| total + vec)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Centers of the cells of a fine grid over the unit square, standing in for
+    // uniform samples so that means over the mappings are nearly exact
+    fn grid() -> impl Iterator<Item = (f32, f32)> {
+        let cells = 200;
+        (0..cells * cells).map(move |index| {
+            let x = (index % cells) as f32 + 0.5;
+            let y = (index / cells) as f32 + 0.5;
+            (x / cells as f32, y / cells as f32)
+        })
+    }
+
+    fn mean(map: impl Fn((f32, f32)) -> f32) -> f32 {
+        let values: Vec<f32> = grid().map(map).collect();
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+
+    fn assert_unit_directions(map: impl Fn((f32, f32)) -> Vec3, min_z: f32) {
+        for sample in grid() {
+            let direction = map(sample);
+            assert!((direction.length() - 1.).abs() < 1e-4, "{direction:?}");
+            assert!(direction.z >= min_z - 1e-5, "{direction:?}");
+        }
+    }
+
+    #[test]
+    fn hemisphere_mappings_follow_their_densities() {
+        // Under a density of cos(theta) / pi the mean cosine is 2 / 3, while a
+        // uniform density over the hemisphere gives 1 / 2
+        assert_unit_directions(Vec3::cosine_hemisphere, 0.);
+        assert!((mean(|sample| Vec3::cosine_hemisphere(sample).z) - 2. / 3.).abs() < 1e-3);
+        assert!(mean(|sample| Vec3::cosine_hemisphere(sample).x).abs() < 1e-3);
+
+        assert_unit_directions(Vec3::uniform_hemisphere, 0.);
+        assert!((mean(|sample| Vec3::uniform_hemisphere(sample).z) - 0.5).abs() < 1e-3);
+        assert!(mean(|sample| Vec3::uniform_hemisphere(sample).y).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sphere_and_cone_mappings_are_uniform() {
+        assert_unit_directions(Vec3::uniform_sphere, -1.);
+        assert!(mean(|sample| Vec3::uniform_sphere(sample).z).abs() < 1e-3);
+        let z_squared = mean(|sample| Vec3::uniform_sphere(sample).z.powi(2));
+        assert!((z_squared - 1. / 3.).abs() < 1e-3);
+
+        // Uniform in solid angle means uniform in cos(theta) between its bounds
+        assert_unit_directions(|sample| Vec3::uniform_cone(sample, 0.8), 0.8);
+        assert!((mean(|sample| Vec3::uniform_cone(sample, 0.8).z) - 0.9).abs() < 1e-3);
+    }
+
+    #[test]
+    fn concentric_disc_preserves_area() {
+        for sample in grid() {
+            let point = Vec3::concentric_disc(sample);
+            assert!(point.length() <= 1. + 1e-5 && point.z == 0.);
+        }
+        // Mean squared radius of a uniform point of the unit disc
+        let radius_squared = mean(|sample| Vec3::concentric_disc(sample).length_squared());
+        assert!((radius_squared - 0.5).abs() < 1e-3);
+
+        // The center and the edges of the square land on the center and the rim
+        assert!(Vec3::concentric_disc((0.5, 0.5)).length() == 0.);
+        assert!((Vec3::concentric_disc((1., 0.5)) - Vec3::new(1., 0., 0.)).length() < 1e-6);
+        assert!((Vec3::concentric_disc((0.5, 1.)) - Vec3::new(0., 1., 0.)).length() < 1e-6);
+    }
+}
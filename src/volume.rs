@@ -1,11 +1,9 @@
-use rand::random;
-
 use crate::{
     hittable::Hittable,
     interval::Interval,
     material::{Isotropic, Material},
     texture::Texture,
-    utilities::random_num,
+    utilities::{random_num, RenderRng},
     vec3::Vec3,
 };
 
@@ -39,13 +37,14 @@ impl Hittable for ConstantMedium {
         ray: crate::ray::Ray,
         interval: crate::interval::Interval,
         hit_data: &mut crate::hittable::HitData,
+        rng: &mut RenderRng,
     ) -> bool {
         let mut hit_data_clone1 = hit_data.clone();
         let mut hit_data_clone2 = hit_data.clone();
 
         if !(self
             .boundary
-            .hit(ray, Interval::UNIVERSE, &mut hit_data_clone1))
+            .hit(ray, Interval::UNIVERSE, &mut hit_data_clone1, rng))
         {
             return false;
         }
@@ -56,6 +55,7 @@ impl Hittable for ConstantMedium {
             ray,
             Interval::new(hit_data_clone1.hit_along_ray + 0.001, f32::INFINITY),
             &mut hit_data_clone2,
+            rng,
         )) {
             return false;
         }
@@ -72,7 +72,7 @@ impl Hittable for ConstantMedium {
         let distance_inside_boundary = (hit_data_clone2.hit_along_ray
             - hit_data_clone1.hit_along_ray)
             * ray.direction.length();
-        let hit_distance = self.neg_inverse_density * random_num().ln();
+        let hit_distance = self.neg_inverse_density * random_num(rng).ln();
 
         // No scattering occurs if the randomly generated number qualifies and the ray passes straight through the volume
         // as if it doesn't hit